@@ -0,0 +1,217 @@
+use sov_modules_api::Context;
+
+use crate::address::UserAddress;
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone)]
+/// Ban of a user from a subreddit, `until_slot` of `None` is permanent
+pub struct Ban<C: Context> {
+    user_address: UserAddress<C>,
+    banned_by: UserAddress<C>,
+    reason: String,
+    created_at_slot: u64,
+    until_slot: Option<u64>,
+}
+
+impl<C: Context> Ban<C> {
+    pub fn new(
+        user_address: UserAddress<C>,
+        banned_by: UserAddress<C>,
+        reason: &str,
+        created_at_slot: u64,
+        until_slot: Option<u64>,
+    ) -> Self {
+        Ban {
+            user_address,
+            banned_by,
+            reason: reason.to_string(),
+            created_at_slot,
+            until_slot,
+        }
+    }
+
+    /// Expired bans are kept in state until a moderator lifts them but no longer apply
+    pub fn is_active(&self, slot_height: u64) -> bool {
+        is_active_until(self.until_slot, slot_height)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_user_address(&self) -> &UserAddress<C> {
+        &self.user_address
+    }
+
+    #[allow(dead_code)]
+    pub fn get_banned_by(&self) -> &UserAddress<C> {
+        &self.banned_by
+    }
+
+    #[allow(dead_code)]
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+
+    #[allow(dead_code)]
+    pub fn get_created_at_slot(&self) -> u64 {
+        self.created_at_slot
+    }
+
+    #[allow(dead_code)]
+    pub fn get_until_slot(&self) -> Option<u64> {
+        self.until_slot
+    }
+}
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone)]
+/// Appeal of a banned user waiting in the moderator queue of a subreddit
+pub struct Appeal<C: Context> {
+    user_address: UserAddress<C>,
+    message: String,
+    created_at_slot: u64,
+}
+
+impl<C: Context> Appeal<C> {
+    pub fn new(user_address: UserAddress<C>, message: &str, created_at_slot: u64) -> Self {
+        Appeal {
+            user_address,
+            message: message.to_string(),
+            created_at_slot,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_user_address(&self) -> &UserAddress<C> {
+        &self.user_address
+    }
+
+    #[allow(dead_code)]
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    #[allow(dead_code)]
+    pub fn get_created_at_slot(&self) -> u64 {
+        self.created_at_slot
+    }
+}
+
+
+/// Bans and mutes apply up to, but not including, their `until_slot`
+pub fn is_active_until(until_slot: Option<u64>, slot_height: u64) -> bool {
+    until_slot.map(|until| slot_height < until).unwrap_or(true)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::StateMapAccessor;
+
+    use crate::test_utils::{error_code, last_event_key, CallResult, Fixture, Member};
+
+    fn ban(f: &mut Fixture, user: &Member, until_slot: Option<u64>) -> CallResult {
+        let owner = f.owner.0.clone();
+        f.reddit.ban_user(f.sub.clone(), user.1.clone(), until_slot, "spam", &owner, &mut f.working_set)
+    }
+
+    fn post(f: &mut Fixture, author: &Member) -> CallResult {
+        f.reddit.create_new_post("title", "flair", "content", f.sub.clone(), &author.0, &mut f.working_set)
+    }
+
+    fn appeal(f: &mut Fixture, user: &Member) -> CallResult {
+        f.reddit.appeal_ban(f.sub.clone(), "sorry", &user.0, &mut f.working_set)
+    }
+
+    fn pending_appeals(f: &mut Fixture) -> usize {
+        f.reddit.appeal_queue.get(&f.sub, &mut f.working_set).unwrap_or_default().len()
+    }
+
+    #[test]
+    fn bans_stop_applying_at_their_until_slot() {
+        let mut f = Fixture::new();
+        let user = f.user("user");
+        f.set_slot(10);
+        ban(&mut f, &user, Some(20)).unwrap();
+
+        f.set_slot(19);
+        assert_eq!(error_code(post(&mut f, &user)), "USER_BANNED");
+
+        f.set_slot(20);
+        post(&mut f, &user).unwrap();
+        // An expired ban can no longer be appealed
+        assert_eq!(error_code(appeal(&mut f, &user)), "BAN_NOT_FOUND");
+    }
+
+    #[test]
+    fn rejects_bans_that_already_ended() {
+        let mut f = Fixture::new();
+        let user = f.user("user");
+        f.set_slot(10);
+
+        assert_eq!(error_code(ban(&mut f, &user, Some(10))), "INVALID_BAN_EXPIRY");
+        assert_eq!(error_code(ban(&mut f, &user, Some(5))), "INVALID_BAN_EXPIRY");
+        assert!(f.reddit.ban_collections.get(&(f.sub.clone(), user.1.clone()), &mut f.working_set).is_none());
+
+        ban(&mut f, &user, Some(11)).unwrap();
+        ban(&mut f, &user, None).unwrap();
+    }
+
+    #[test]
+    fn rejects_bans_in_archived_subreddits() {
+        let mut f = Fixture::new();
+        let (owner, user) = (f.owner.clone(), f.user("user"));
+        f.reddit.archive_subreddit(f.sub.clone(), &owner.0, &mut f.working_set).unwrap();
+
+        assert_eq!(error_code(ban(&mut f, &user, None)), "SUBREDDIT_ARCHIVED");
+    }
+
+    #[test]
+    fn muted_users_cannot_appeal_until_the_mute_ends() {
+        let mut f = Fixture::new();
+        let (owner, user) = (f.owner.clone(), f.user("user"));
+        ban(&mut f, &user, None).unwrap();
+        f.reddit
+            .mute_user(f.sub.clone(), user.1.clone(), Some(5), &owner.0, &mut f.working_set)
+            .unwrap();
+
+        f.set_slot(4);
+        assert_eq!(error_code(appeal(&mut f, &user)), "USER_MUTED");
+        assert_eq!(pending_appeals(&mut f), 0);
+
+        f.set_slot(5);
+        appeal(&mut f, &user).unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "BanAppealed");
+        assert_eq!(error_code(appeal(&mut f, &user)), "APPEAL_ALREADY_PENDING");
+    }
+
+    #[test]
+    fn dismissing_an_appeal_keeps_the_ban() {
+        let mut f = Fixture::new();
+        let (owner, user) = (f.owner.clone(), f.user("user"));
+        ban(&mut f, &user, None).unwrap();
+        appeal(&mut f, &user).unwrap();
+
+        f.reddit
+            .dismiss_appeal(f.sub.clone(), user.1.clone(), &owner.0, &mut f.working_set)
+            .unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "AppealDismissed");
+        assert_eq!(pending_appeals(&mut f), 0);
+        assert_eq!(error_code(post(&mut f, &user)), "USER_BANNED");
+
+        let again = f.reddit.dismiss_appeal(f.sub.clone(), user.1.clone(), &owner.0, &mut f.working_set);
+        assert_eq!(error_code(again), "APPEAL_NOT_FOUND");
+
+        // The user may appeal again after a dismissal
+        appeal(&mut f, &user).unwrap();
+        assert_eq!(pending_appeals(&mut f), 1);
+    }
+}
@@ -1,8 +1,8 @@
 use anyhow::{bail, Result};
 #[cfg(feature = "native")]
 use sov_modules_api::macros::CliWalletArg;
-use sov_modules_api::{CallResponse, Context, StateMapAccessor, WorkingSet};
-use crate::{address::{SubAddress, UserAddress}, post::Post, subreddit::SubReddit, user::User, Reddit};
+use sov_modules_api::{CallResponse, Context, StateMapAccessor, StateValueAccessor, WorkingSet};
+use crate::{address::{CommentAddress, PostAddress, SubAddress, UserAddress}, ban::{is_active_until, Appeal, Ban}, comment::Comment, error::RedditError, event::RedditEvent, moderator::ModPermission, post::{Post, PostStatus}, ratelimit::RateLimitedAction, subreddit::SubReddit, user::User, utils::{normalize_subname, normalize_username}, vote::VoteDirection, Reddit};
 
 
 
@@ -32,6 +32,122 @@ pub enum CallMessage<C: Context> {
         flair: String,
         content: String,
         subaddress: C::Address
+    },
+
+    /// Fields left as `None` keep their current value
+    EditPost {
+        post_address: C::Address,
+        content: Option<String>,
+        flair: Option<String>,
+    },
+
+    DeletePost {
+        post_address: C::Address,
+    },
+
+    /// Only the owner can archive a subreddit, it stops accepting posts for good
+    ArchiveSubreddit {
+        sub_address: C::Address,
+    },
+
+    /// Lets moderators with the flair permission change the flair of any post
+    SetPostFlair {
+        post_address: C::Address,
+        flair: String,
+    },
+
+    /// `None` falls back to the global setting of the rollup
+    SetArchiveAfterSlots {
+        sub_address: C::Address,
+        archive_after_slots: Option<u64>,
+    },
+
+    /// `user_address` is the user profile address of the new moderator
+    AddModerator {
+        sub_address: C::Address,
+        user_address: C::Address,
+        permissions: Vec<ModPermission>,
+    },
+
+    /// Replaces the permission set of a junior moderator
+    SetModeratorPermissions {
+        sub_address: C::Address,
+        user_address: C::Address,
+        permissions: Vec<ModPermission>,
+    },
+
+    RemoveModerator {
+        sub_address: C::Address,
+        user_address: C::Address,
+    },
+
+    LeaveModerator {
+        sub_address: C::Address,
+    },
+
+    /// The new owner has to be a moderator of the subreddit already
+    TransferOwnership {
+        sub_address: C::Address,
+        user_address: C::Address,
+    },
+
+    /// `until_slot` of `None` bans the user permanently
+    BanUser {
+        sub_address: C::Address,
+        user_address: C::Address,
+        until_slot: Option<u64>,
+        reason: String,
+    },
+
+    UnbanUser {
+        sub_address: C::Address,
+        user_address: C::Address,
+    },
+
+    /// Muted users cannot appeal their ban until the mute expires
+    MuteUser {
+        sub_address: C::Address,
+        user_address: C::Address,
+        until_slot: Option<u64>,
+    },
+
+    AppealBan {
+        sub_address: C::Address,
+        message: String,
+    },
+
+    DismissAppeal {
+        sub_address: C::Address,
+        user_address: C::Address,
+    },
+
+    Subscribe {
+        sub_address: C::Address,
+    },
+
+    Unsubscribe {
+        sub_address: C::Address,
+    },
+
+    CreateComment {
+        post_address: C::Address,
+        parent_comment: Option<C::Address>,
+        content: String,
+    },
+
+    EditComment {
+        comment_address: C::Address,
+        content: String,
+    },
+
+    /// Also removes comments of others when the sender moderates the subreddit with `MANAGE_POSTS`
+    DeleteComment {
+        comment_address: C::Address,
+    },
+
+    Vote {
+        post_address: C::Address,
+        direction: VoteDirection,
     }
 }
 
@@ -44,29 +160,72 @@ impl<C: Context> Reddit<C> {
         context: &C,
         working_set: &mut WorkingSet<C>,
     ) -> Result<CallResponse>{
-        let (new_user_address , new_user) = User::new(username, &self.user_collections, context, working_set)?;
+        if let Some(existing) = self.user_address_collections.get(context.sender(), working_set) {
+            bail!(RedditError::SenderAlreadyRegistered {
+                sender: context.sender().to_string(),
+                user_address: existing.to_string(),
+            });
+        }
+
+        let canonical_username = normalize_username(username)?;
+        self.ensure_name_not_reserved(&canonical_username, working_set)?;
+
+        if self.username_collections.get(&canonical_username, working_set).is_some() {
+            bail!(RedditError::UserAlreadyExists { username: username.to_string() });
+        }
 
-        
-            //self.user_address_collections.set(context.sender(), &new_user_address, working_set);
+        let (new_user_address , new_user) = User::new(username, &canonical_username, &self.user_collections, context, self.now(working_set), working_set)?;
+
+        self.user_address_collections.set(context.sender(), &new_user_address, working_set);
+        self.username_collections.set(&canonical_username, &new_user_address, working_set);
         self.user_collections.set(&new_user_address, &new_user, working_set);
 
+        self.emit_event(
+            RedditEvent::UserCreated {
+                user_address: new_user_address,
+                username: username.to_string(),
+                owner: context.sender().clone(),
+            },
+            working_set,
+        );
+
           Ok(CallResponse::default())
     }
 
 
     pub(crate) fn create_new_subreddit(
         &self,
+        user_address: UserAddress<C>,
         subname: &str,
         description: &str,
         context: &C,
         working_set: &mut WorkingSet<C>,
     ) -> Result<CallResponse> {
 
+        let sender_user = self.ensure_registered_sender(context, working_set)?;
+
+        if sender_user != user_address {
+            bail!(RedditError::NotUserOwner { user_address: user_address.to_string() });
+        }
 
-           let (new_sub_address , new_sub) = SubReddit::new(subname, description, &self.sub_collections, context, working_set)?;
+        let canonical_subname = normalize_subname(subname)?;
+        self.ensure_name_not_reserved(&canonical_subname, working_set)?;
+        self.check_rate_limit(&user_address, RateLimitedAction::CREATE_SUBREDDIT, working_set)?;
+
+           let (new_sub_address , new_sub) = SubReddit::new(subname, &canonical_subname, description, &self.sub_collections, user_address.clone(), self.now(working_set), working_set)?;
 
         self.sub_collections.set(&new_sub_address, &new_sub, working_set);
 
+        self.emit_event(
+            RedditEvent::SubredditCreated {
+                sub_address: new_sub_address,
+                subname: subname.to_string(),
+                description: description.to_string(),
+                creator: user_address,
+            },
+            working_set,
+        );
+
           Ok(CallResponse::default())
 
 
@@ -83,10 +242,56 @@ impl<C: Context> Reddit<C> {
         working_set: &mut WorkingSet<C>,
     ) -> Result<CallResponse> {
 
+        let author = self.ensure_registered_sender(context, working_set)?;
+
+        let sub = self
+            .sub_collections
+            .get(&subaddress, working_set)
+            .ok_or_else(|| RedditError::SubredditNotFound { sub: subaddress.to_string() })?;
+
+        if sub.is_archived() {
+            bail!(RedditError::SubredditArchived { sub: subaddress.to_string() });
+        }
+
+        self.ensure_not_banned(&subaddress, &author, working_set)?;
+        self.check_rate_limit(&author, RateLimitedAction::CREATE_POST, working_set)?;
+
+        let nonce = self.post_nonce_collections.get(&author, working_set).unwrap_or_default();
+        let now = self.now(working_set);
+        let slot_height = now.slot_height;
 
-           let (new_post_address , new_post) = Post::new(title, flair, content, subaddress , context, working_set)?;
+           let (new_post_address , new_post) = Post::new(title, flair, content, subaddress.clone() , author.clone(), nonce, now)?;
 
         self.post_collections.set(&new_post_address, &new_post, working_set);
+        self.post_nonce_collections.set(&author, &(nonce + 1), working_set);
+        self.queue_rank_update(&new_post, working_set);
+        self.user_post_index.set(&(author.clone(), nonce), &new_post_address, working_set);
+
+        let sub_post_count = self.sub_post_counts.get(&subaddress, working_set).unwrap_or_default();
+        self.sub_post_index.set(&(subaddress.clone(), sub_post_count), &new_post_address, working_set);
+        self.sub_post_counts.set(&subaddress, &(sub_post_count + 1), working_set);
+
+        let archive_after_slots = sub
+            .get_archive_after_slots()
+            .or_else(|| self.archive_after_slots.get(working_set));
+        if let Some(archive_after_slots) = archive_after_slots {
+            let archive_at = slot_height.saturating_add(archive_after_slots);
+            let mut queue = self.archive_queue.get(&archive_at, working_set).unwrap_or_default();
+            queue.push(new_post_address.clone());
+            self.archive_queue.set(&archive_at, &queue, working_set);
+        }
+
+        self.emit_event(
+            RedditEvent::PostCreated {
+                post_address: new_post_address,
+                sub_address: subaddress,
+                author,
+                title: title.to_string(),
+                flair: flair.to_string(),
+                content: content.to_string(),
+            },
+            working_set,
+        );
 
           Ok(CallResponse::default())
 
@@ -94,8 +299,990 @@ impl<C: Context> Reddit<C> {
     }
 
 
+    pub(crate) fn edit_post(
+        &self,
+        post_address: PostAddress<C>,
+        content: Option<&str>,
+        flair: Option<&str>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let editor = self.ensure_registered_sender(context, working_set)?;
+        let mut post = self.get_active_post(&post_address, working_set)?;
+
+        if post.get_user_address() != &editor {
+            bail!(RedditError::NotAuthor { address: post_address.to_string() });
+        }
+
+        post.edit(content, flair, self.now(working_set));
+        self.post_collections.set(&post_address, &post, working_set);
+
+        self.emit_event(
+            RedditEvent::PostEdited {
+                post_address,
+                content: post.get_post_content().to_string(),
+                flair: post.get_post_flair().to_string(),
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn delete_post(
+        &self,
+        post_address: PostAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let deleted_by = self.ensure_registered_sender(context, working_set)?;
+        let mut post = self
+            .post_collections
+            .get(&post_address, working_set)
+            .ok_or_else(|| RedditError::PostNotFound { post: post_address.to_string() })?;
+
+        if post.get_post_status() == PostStatus::DELETED {
+            bail!(RedditError::PostNotActive {
+                post: post_address.to_string(),
+                status: post.get_post_status().to_string(),
+            });
+        }
 
+        let can_manage_posts = self
+            .sub_collections
+            .get(post.get_sub_address(), working_set)
+            .map(|sub| sub.has_permission(&deleted_by, ModPermission::MANAGE_POSTS))
+            .unwrap_or(false);
 
+        if post.get_user_address() != &deleted_by && !can_manage_posts {
+            bail!(RedditError::NotAuthorOrModerator { address: post_address.to_string() });
+        }
 
+        post.mark_deleted();
+        self.post_collections.set(&post_address, &post, working_set);
+        self.queue_rank_update(&post, working_set);
 
-}
\ No newline at end of file
+        self.emit_event(RedditEvent::PostDeleted { post_address, deleted_by }, working_set);
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Only applies to posts created afterwards, already scheduled posts keep their archive slot
+    pub(crate) fn set_archive_after_slots(
+        &self,
+        sub_address: SubAddress<C>,
+        archive_after_slots: Option<u64>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let sender_user = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &sender_user, ModPermission::MANAGE_SETTINGS)?;
+
+        sub.set_archive_after_slots(archive_after_slots, self.now(working_set));
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(RedditEvent::SubredditSettingsUpdated { sub_address }, working_set);
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn archive_subreddit(
+        &self,
+        sub_address: SubAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let archived_by = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        if sub.get_owner() != &archived_by {
+            bail!(RedditError::NotSubredditOwner { sub: sub_address.to_string() });
+        }
+
+        if sub.is_archived() {
+            bail!(RedditError::SubredditArchived { sub: sub_address.to_string() });
+        }
+
+        sub.archive(self.now(working_set));
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(RedditEvent::SubredditArchived { sub_address, archived_by }, working_set);
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Moderators can only grant permissions they hold themselves
+    pub(crate) fn add_moderator(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        permissions: Vec<ModPermission>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let added_by = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &added_by, ModPermission::MANAGE_MODS)?;
+        ensure_can_grant(&sub, &added_by, &permissions)?;
+
+        if self.user_collections.get(&user_address, working_set).is_none() {
+            bail!(RedditError::UserNotFound { user: user_address.to_string() });
+        }
+
+        if sub.is_moderator(&user_address) {
+            bail!(RedditError::AlreadyModerator {
+                sub: sub_address.to_string(),
+                user: user_address.to_string(),
+            });
+        }
+
+        sub.add_moderator(user_address.clone(), &permissions);
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(
+            RedditEvent::ModeratorAdded {
+                sub_address,
+                moderator: user_address,
+                permissions,
+                added_by,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Moderators can only remove moderators that are junior to them
+    pub(crate) fn remove_moderator(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let removed_by = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &removed_by, ModPermission::MANAGE_MODS)?;
+        ensure_senior(&sub, &removed_by, &user_address)?;
+
+        sub.remove_moderator(&user_address);
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(
+            RedditEvent::ModeratorRemoved {
+                sub_address,
+                moderator: user_address,
+                removed_by,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn set_moderator_permissions(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        permissions: Vec<ModPermission>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let updated_by = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &updated_by, ModPermission::MANAGE_MODS)?;
+        ensure_senior(&sub, &updated_by, &user_address)?;
+        ensure_can_grant(&sub, &updated_by, &permissions)?;
+
+        sub.set_moderator_permissions(&user_address, &permissions);
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(
+            RedditEvent::ModeratorPermissionsUpdated {
+                sub_address,
+                moderator: user_address,
+                permissions,
+                updated_by,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn leave_moderator(
+        &self,
+        sub_address: SubAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let moderator = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        if !sub.is_moderator(&moderator) {
+            bail!(RedditError::NotModerator { sub: sub_address.to_string() });
+        }
+
+        if sub.get_owner() == &moderator {
+            bail!(RedditError::OwnerCannotLeave { sub: sub_address.to_string() });
+        }
+
+        sub.remove_moderator(&moderator);
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(
+            RedditEvent::ModeratorRemoved {
+                sub_address,
+                moderator: moderator.clone(),
+                removed_by: moderator,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn transfer_ownership(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let previous_owner = self.ensure_registered_sender(context, working_set)?;
+        let mut sub = self.get_sub(&sub_address, working_set)?;
+
+        if sub.get_owner() != &previous_owner {
+            bail!(RedditError::NotSubredditOwner { sub: sub_address.to_string() });
+        }
+
+        if !sub.is_moderator(&user_address) {
+            bail!(RedditError::TargetNotModerator {
+                sub: sub_address.to_string(),
+                user: user_address.to_string(),
+            });
+        }
+
+        sub.transfer_ownership(user_address.clone());
+        self.sub_collections.set(&sub_address, &sub, working_set);
+
+        self.emit_event(
+            RedditEvent::OwnershipTransferred {
+                sub_address,
+                previous_owner,
+                new_owner: user_address,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn set_post_flair(
+        &self,
+        post_address: PostAddress<C>,
+        flair: &str,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let moderator = self.ensure_registered_sender(context, working_set)?;
+        let mut post = self.get_active_post(&post_address, working_set)?;
+        let sub = self.get_sub(post.get_sub_address(), working_set)?;
+
+        ensure_permission(&sub, &moderator, ModPermission::MANAGE_FLAIR)?;
+
+        post.edit(None, Some(flair), self.now(working_set));
+        self.post_collections.set(&post_address, &post, working_set);
+
+        self.emit_event(
+            RedditEvent::PostEdited {
+                post_address,
+                content: post.get_post_content().to_string(),
+                flair: post.get_post_flair().to_string(),
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Banning again replaces the previous ban
+    pub(crate) fn ban_user(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        until_slot: Option<u64>,
+        reason: &str,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let banned_by = self.ensure_registered_sender(context, working_set)?;
+        let sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &banned_by, ModPermission::MANAGE_USERS)?;
+
+        if sub.is_archived() {
+            bail!(RedditError::SubredditArchived { sub: sub_address.to_string() });
+        }
+
+        let slot_height = self.current_slot_height.get(working_set).unwrap_or_default();
+        if let Some(until_slot) = until_slot {
+            // A ban that already ended would be recorded without ever applying
+            if until_slot <= slot_height {
+                bail!(RedditError::InvalidBanExpiry { until_slot, slot_height });
+            }
+        }
+
+        if self.user_collections.get(&user_address, working_set).is_none() {
+            bail!(RedditError::UserNotFound { user: user_address.to_string() });
+        }
+
+        if sub.is_moderator(&user_address) {
+            bail!(RedditError::CannotBanModerator {
+                sub: sub_address.to_string(),
+                user: user_address.to_string(),
+            });
+        }
+
+        let ban = Ban::new(user_address.clone(), banned_by.clone(), reason, slot_height, until_slot);
+        let ban_key = (sub_address.clone(), user_address.clone());
+
+        if self.ban_collections.get(&ban_key, working_set).is_none() {
+            let mut ban_list = self.sub_ban_list.get(&sub_address, working_set).unwrap_or_default();
+            ban_list.push(user_address.clone());
+            self.sub_ban_list.set(&sub_address, &ban_list, working_set);
+        }
+        self.ban_collections.set(&ban_key, &ban, working_set);
+
+        self.emit_event(
+            RedditEvent::UserBanned {
+                sub_address,
+                user_address,
+                banned_by,
+                until_slot,
+                reason: reason.to_string(),
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Lifting a ban also resolves a pending appeal of the user
+    pub(crate) fn unban_user(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let unbanned_by = self.ensure_registered_sender(context, working_set)?;
+        let sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &unbanned_by, ModPermission::MANAGE_USERS)?;
+
+        let ban_key = (sub_address.clone(), user_address.clone());
+        if self.ban_collections.get(&ban_key, working_set).is_none() {
+            bail!(RedditError::BanNotFound {
+                sub: sub_address.to_string(),
+                user: user_address.to_string(),
+            });
+        }
+
+        self.ban_collections.remove(&ban_key, working_set);
+
+        let mut ban_list = self.sub_ban_list.get(&sub_address, working_set).unwrap_or_default();
+        ban_list.retain(|u| u != &user_address);
+        self.sub_ban_list.set(&sub_address, &ban_list, working_set);
+
+        self.remove_appeal(&sub_address, &user_address, working_set);
+
+        self.emit_event(
+            RedditEvent::UserUnbanned {
+                sub_address,
+                user_address,
+                unbanned_by,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn mute_user(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        until_slot: Option<u64>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let muted_by = self.ensure_registered_sender(context, working_set)?;
+        let sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &muted_by, ModPermission::MANAGE_USERS)?;
+
+        if self.user_collections.get(&user_address, working_set).is_none() {
+            bail!(RedditError::UserNotFound { user: user_address.to_string() });
+        }
+
+        self.mute_collections
+            .set(&(sub_address.clone(), user_address.clone()), &until_slot, working_set);
+
+        self.emit_event(
+            RedditEvent::UserMuted {
+                sub_address,
+                user_address,
+                muted_by,
+                until_slot,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn appeal_ban(
+        &self,
+        sub_address: SubAddress<C>,
+        message: &str,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let user_address = self.ensure_registered_sender(context, working_set)?;
+        let slot_height = self.current_slot_height.get(working_set).unwrap_or_default();
+        let key = (sub_address.clone(), user_address.clone());
+
+        let has_active_ban = self
+            .ban_collections
+            .get(&key, working_set)
+            .map(|ban| ban.is_active(slot_height))
+            .unwrap_or(false);
+
+        if !has_active_ban {
+            bail!(RedditError::BanNotFound {
+                sub: sub_address.to_string(),
+                user: user_address.to_string(),
+            });
+        }
+
+        if let Some(until_slot) = self.mute_collections.get(&key, working_set) {
+            if is_active_until(until_slot, slot_height) {
+                bail!(RedditError::UserMuted { sub: sub_address.to_string() });
+            }
+        }
+
+        let mut queue = self.appeal_queue.get(&sub_address, working_set).unwrap_or_default();
+        if queue.iter().any(|appeal| appeal.get_user_address() == &user_address) {
+            bail!(RedditError::AppealAlreadyPending { sub: sub_address.to_string() });
+        }
+
+        queue.push(Appeal::new(user_address.clone(), message, slot_height));
+        self.appeal_queue.set(&sub_address, &queue, working_set);
+
+        self.emit_event(
+            RedditEvent::BanAppealed {
+                sub_address,
+                user_address,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Rejects an appeal, the ban stays in place
+    pub(crate) fn dismiss_appeal(
+        &self,
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let dismissed_by = self.ensure_registered_sender(context, working_set)?;
+        let sub = self.get_sub(&sub_address, working_set)?;
+
+        ensure_permission(&sub, &dismissed_by, ModPermission::MANAGE_USERS)?;
+
+        if !self.remove_appeal(&sub_address, &user_address, working_set) {
+            bail!(RedditError::AppealNotFound {
+                sub: sub_address.to_string(),
+                user: user_address.to_string(),
+            });
+        }
+
+        self.emit_event(
+            RedditEvent::AppealDismissed {
+                sub_address,
+                user_address,
+                dismissed_by,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn subscribe(
+        &self,
+        sub_address: SubAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let user_address = self.ensure_registered_sender(context, working_set)?;
+        self.get_sub(&sub_address, working_set)?;
+
+        let mut subscriptions = self
+            .subscription_collections
+            .get(&user_address, working_set)
+            .unwrap_or_default();
+
+        if subscriptions.contains(&sub_address) {
+            bail!(RedditError::AlreadySubscribed { sub: sub_address.to_string() });
+        }
+
+        subscriptions.push(sub_address.clone());
+        self.subscription_collections.set(&user_address, &subscriptions, working_set);
+
+        let count = self.subscriber_counts.get(&sub_address, working_set).unwrap_or_default();
+        self.subscriber_counts.set(&sub_address, &(count + 1), working_set);
+
+        self.emit_event(
+            RedditEvent::Subscribed {
+                sub_address,
+                user_address,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn unsubscribe(
+        &self,
+        sub_address: SubAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let user_address = self.ensure_registered_sender(context, working_set)?;
+
+        let mut subscriptions = self
+            .subscription_collections
+            .get(&user_address, working_set)
+            .unwrap_or_default();
+
+        if !subscriptions.contains(&sub_address) {
+            bail!(RedditError::NotSubscribed { sub: sub_address.to_string() });
+        }
+
+        subscriptions.retain(|s| s != &sub_address);
+        self.subscription_collections.set(&user_address, &subscriptions, working_set);
+
+        let count = self.subscriber_counts.get(&sub_address, working_set).unwrap_or_default();
+        self.subscriber_counts.set(&sub_address, &count.saturating_sub(1), working_set);
+
+        self.emit_event(
+            RedditEvent::Unsubscribed {
+                sub_address,
+                user_address,
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn create_new_comment(
+        &self,
+        post_address: PostAddress<C>,
+        parent_comment: Option<CommentAddress<C>>,
+        content: &str,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let author = self.ensure_registered_sender(context, working_set)?;
+
+        let post = self.get_active_post(&post_address, working_set)?;
+        self.ensure_not_banned(post.get_sub_address(), &author, working_set)?;
+        self.check_rate_limit(&author, RateLimitedAction::CREATE_COMMENT, working_set)?;
+
+        let new_comment_address = match parent_comment.clone() {
+            Some(parent_address) => {
+                let mut parent = self
+                    .comment_collections
+                    .get(&parent_address, working_set)
+                    .ok_or_else(|| RedditError::CommentNotFound { comment: parent_address.to_string() })?;
+
+                if parent.get_post_address() != &post_address {
+                    bail!(RedditError::CommentNotInPost {
+                        comment: parent_address.to_string(),
+                        post: post_address.to_string(),
+                    });
+                }
+
+                if parent.is_deleted() {
+                    bail!(RedditError::CommentDeleted { comment: parent_address.to_string() });
+                }
+
+                let (new_comment_address, new_comment) = Comment::new(
+                    content,
+                    post_address.clone(),
+                    Some(&parent),
+                    parent.get_replies().len(),
+                    author.clone(),
+                )?;
+
+                parent.add_reply(new_comment_address.clone());
+                self.comment_collections.set(&parent_address, &parent, working_set);
+                self.comment_collections.set(&new_comment_address, &new_comment, working_set);
+                new_comment_address
+            }
+            None => {
+                let mut top_level = self
+                    .post_comment_collections
+                    .get(&post_address, working_set)
+                    .unwrap_or_default();
+
+                let (new_comment_address, new_comment) =
+                    Comment::new(content, post_address.clone(), None, top_level.len(), author.clone())?;
+
+                top_level.push(new_comment_address.clone());
+                self.post_comment_collections.set(&post_address, &top_level, working_set);
+                self.comment_collections.set(&new_comment_address, &new_comment, working_set);
+                new_comment_address
+            }
+        };
+
+        self.emit_event(
+            RedditEvent::CommentCreated {
+                comment_address: new_comment_address,
+                post_address,
+                parent_comment,
+                author,
+                content: content.to_string(),
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn edit_comment(
+        &self,
+        comment_address: CommentAddress<C>,
+        content: &str,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let mut comment = self.get_own_comment(&comment_address, context, working_set)?;
+
+        comment.set_content(content);
+        self.comment_collections.set(&comment_address, &comment, working_set);
+
+        self.emit_event(
+            RedditEvent::CommentEdited {
+                comment_address,
+                content: content.to_string(),
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    /// Authors can delete their own comments, moderators with `MANAGE_POSTS` can remove any comment of their subreddit
+    pub(crate) fn delete_comment(
+        &self,
+        comment_address: CommentAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let deleted_by = self.ensure_registered_sender(context, working_set)?;
+        let mut comment = self.get_live_comment(&comment_address, working_set)?;
+
+        let can_manage_posts = self
+            .post_collections
+            .get(comment.get_post_address(), working_set)
+            .and_then(|post| self.sub_collections.get(post.get_sub_address(), working_set))
+            .map(|sub| sub.has_permission(&deleted_by, ModPermission::MANAGE_POSTS))
+            .unwrap_or(false);
+
+        if comment.get_user_address() != &deleted_by && !can_manage_posts {
+            bail!(RedditError::NotAuthorOrModerator { address: comment_address.to_string() });
+        }
+
+        comment.mark_deleted();
+        self.comment_collections.set(&comment_address, &comment, working_set);
+
+        self.emit_event(RedditEvent::CommentDeleted { comment_address }, working_set);
+
+        Ok(CallResponse::default())
+    }
+
+
+    pub(crate) fn vote_on_post(
+        &self,
+        post_address: PostAddress<C>,
+        direction: VoteDirection,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<CallResponse> {
+        let mut post = self.get_active_post(&post_address, working_set)?;
+
+        let voter = self.ensure_registered_sender(context, working_set)?;
+        self.ensure_not_banned(post.get_sub_address(), &voter, working_set)?;
+
+        let vote_key = (post_address.clone(), voter.clone());
+        let previous = self
+            .post_vote_collections
+            .get(&vote_key, working_set)
+            .unwrap_or(VoteDirection::NONE);
+
+        if previous == direction {
+            return Ok(CallResponse::default());
+        }
+
+        let delta = post.apply_vote(previous, direction);
+        self.post_collections.set(&post_address, &post, working_set);
+        self.queue_rank_update(&post, working_set);
+
+        match direction {
+            VoteDirection::NONE => self.post_vote_collections.remove(&vote_key, working_set),
+            _ => self.post_vote_collections.set(&vote_key, &direction, working_set),
+        }
+
+        let author_address = post.get_user_address();
+        if let Some(mut author_user) = self.user_collections.get(author_address, working_set) {
+            author_user.add_karma(delta);
+            self.user_collections.set(author_address, &author_user, working_set);
+        }
+
+        self.emit_event(
+            RedditEvent::PostVoted {
+                post_address,
+                voter,
+                direction,
+                score: post.get_score(),
+            },
+            working_set,
+        );
+
+        Ok(CallResponse::default())
+    }
+
+
+    fn ensure_name_not_reserved(
+        &self,
+        canonical_name: &str,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<()> {
+        let reserved_names = self.reserved_names.get(working_set).unwrap_or_default();
+
+        if reserved_names.iter().any(|reserved| reserved == canonical_name) {
+            bail!(RedditError::NameReserved { name: canonical_name.to_string() });
+        }
+
+        Ok(())
+    }
+
+
+    fn ensure_not_banned(
+        &self,
+        sub_address: &SubAddress<C>,
+        user: &UserAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<()> {
+        let slot_height = self.current_slot_height.get(working_set).unwrap_or_default();
+
+        if let Some(ban) = self.ban_collections.get(&(sub_address.clone(), user.clone()), working_set) {
+            if ban.is_active(slot_height) {
+                bail!(RedditError::UserBanned { sub: sub_address.to_string() });
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Returns whether the user had a pending appeal
+    fn remove_appeal(
+        &self,
+        sub_address: &SubAddress<C>,
+        user: &UserAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> bool {
+        let mut queue = self.appeal_queue.get(sub_address, working_set).unwrap_or_default();
+        let pending = queue.len();
+        queue.retain(|appeal| appeal.get_user_address() != user);
+
+        if queue.len() == pending {
+            return false;
+        }
+
+        self.appeal_queue.set(sub_address, &queue, working_set);
+        true
+    }
+
+
+    fn get_sub(
+        &self,
+        sub_address: &SubAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<SubReddit<C>> {
+        self.sub_collections
+            .get(sub_address, working_set)
+            .ok_or_else(|| RedditError::SubredditNotFound { sub: sub_address.to_string() }.into())
+    }
+
+
+    /// Loads a post that still accepts edits, votes and comments
+    fn get_active_post(
+        &self,
+        post_address: &PostAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<Post<C>> {
+        let post = self
+            .post_collections
+            .get(post_address, working_set)
+            .ok_or_else(|| RedditError::PostNotFound { post: post_address.to_string() })?;
+
+        if post.get_post_status() != PostStatus::ACTIVE {
+            bail!(RedditError::PostNotActive {
+                post: post_address.to_string(),
+                status: post.get_post_status().to_string(),
+            });
+        }
+
+        Ok(post)
+    }
+
+
+    /// Returns the user profile of the sender, failing if it has not created one
+    fn ensure_registered_sender(
+        &self,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<UserAddress<C>> {
+        self.user_address_collections
+            .get(context.sender(), working_set)
+            .ok_or_else(|| RedditError::SenderNotRegistered { sender: context.sender().to_string() }.into())
+    }
+
+
+    /// Loads a comment that has not been deleted
+    fn get_live_comment(
+        &self,
+        comment_address: &CommentAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<Comment<C>> {
+        let comment = self
+            .comment_collections
+            .get(comment_address, working_set)
+            .ok_or_else(|| RedditError::CommentNotFound { comment: comment_address.to_string() })?;
+
+        if comment.is_deleted() {
+            bail!(RedditError::CommentDeleted { comment: comment_address.to_string() });
+        }
+
+        Ok(comment)
+    }
+
+
+    /// Loads a live comment and checks that the sender is its author
+    fn get_own_comment(
+        &self,
+        comment_address: &CommentAddress<C>,
+        context: &C,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<Comment<C>> {
+        let comment = self.get_live_comment(comment_address, working_set)?;
+
+        if comment.get_user_address() != &self.ensure_registered_sender(context, working_set)? {
+            bail!(RedditError::NotAuthor { address: comment_address.to_string() });
+        }
+
+        Ok(comment)
+    }
+}
+
+
+fn ensure_permission<C: Context>(
+    sub: &SubReddit<C>,
+    user: &UserAddress<C>,
+    permission: ModPermission,
+) -> Result<()> {
+    if !sub.is_moderator(user) {
+        bail!(RedditError::NotModerator { sub: sub.get_sub_address().to_string() });
+    }
+
+    if !sub.has_permission(user, permission) {
+        bail!(RedditError::MissingPermission {
+            sub: sub.get_sub_address().to_string(),
+            permission: permission.as_str().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+
+fn ensure_can_grant<C: Context>(
+    sub: &SubReddit<C>,
+    user: &UserAddress<C>,
+    permissions: &[ModPermission],
+) -> Result<()> {
+    for permission in permissions {
+        ensure_permission(sub, user, *permission)?;
+    }
+    Ok(())
+}
+
+
+/// Moderators can only act on moderators that are junior to them
+fn ensure_senior<C: Context>(
+    sub: &SubReddit<C>,
+    user: &UserAddress<C>,
+    target: &UserAddress<C>,
+) -> Result<()> {
+    let sender_rank = sub
+        .moderator_rank(user)
+        .ok_or_else(|| RedditError::NotModerator { sub: sub.get_sub_address().to_string() })?;
+
+    let target_rank = sub.moderator_rank(target).ok_or_else(|| RedditError::TargetNotModerator {
+        sub: sub.get_sub_address().to_string(),
+        user: target.to_string(),
+    })?;
+
+    if target_rank <= sender_rank {
+        bail!(RedditError::NotSeniorModerator {
+            sub: sub.get_sub_address().to_string(),
+            moderator: target.to_string(),
+        });
+    }
+
+    Ok(())
+}
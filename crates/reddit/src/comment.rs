@@ -0,0 +1,241 @@
+use sov_modules_api::Context;
+
+use crate::{address::{CommentAddress, PostAddress, UserAddress}, error::RedditError, utils::get_comment_address};
+
+
+/// Deepest level a reply can be nested at, top level comments sit at depth 0
+pub const MAX_COMMENT_DEPTH: u32 = 16;
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Clone)]
+/// Defines a comment on a post, optionally replying to another comment
+pub struct Comment<C: Context> {
+    comment_address: CommentAddress<C>,
+    user_address: UserAddress<C>,
+    post_address: PostAddress<C>,
+    parent_comment: Option<CommentAddress<C>>,
+    content: String,
+    depth: u32,
+    replies: Vec<CommentAddress<C>>,
+    deleted: bool,
+}
+
+impl<C: Context> Comment<C> {
+    pub fn new(
+        content: &str,
+        post_address: PostAddress<C>,
+        parent: Option<&Comment<C>>,
+        sibling_count: usize,
+        author: UserAddress<C>,
+    ) -> anyhow::Result<(CommentAddress<C>, Comment<C>)> {
+        let depth = parent.map(|p| p.depth + 1).unwrap_or(0);
+        if depth > MAX_COMMENT_DEPTH {
+            anyhow::bail!(RedditError::CommentTooDeep { max_depth: MAX_COMMENT_DEPTH });
+        }
+
+        let parent_comment = parent.map(|p| p.comment_address.clone());
+
+        let comment_address = get_comment_address(
+            author.as_ref(),
+            post_address.as_ref(),
+            parent_comment.as_ref().map(|p| p.as_ref()),
+            sibling_count as u64,
+        );
+
+        Ok((
+            comment_address.clone(),
+            Comment {
+                comment_address,
+                user_address: author,
+                post_address,
+                parent_comment,
+                content: content.to_string(),
+                depth,
+                replies: vec![],
+                deleted: false,
+            },
+        ))
+    }
+
+    pub(crate) fn add_reply(&mut self, reply: CommentAddress<C>) {
+        self.replies.push(reply);
+    }
+
+    pub(crate) fn set_content(&mut self, content: &str) {
+        self.content = content.to_string();
+    }
+
+    /// Deleted comments keep their place in the tree so replies stay reachable
+    pub(crate) fn mark_deleted(&mut self) {
+        self.content = String::new();
+        self.deleted = true;
+    }
+
+    #[allow(dead_code)]
+    pub fn get_comment_address(&self) -> &CommentAddress<C> {
+        &self.comment_address
+    }
+
+    #[allow(dead_code)]
+    pub fn get_user_address(&self) -> &UserAddress<C> {
+        &self.user_address
+    }
+
+    #[allow(dead_code)]
+    pub fn get_post_address(&self) -> &PostAddress<C> {
+        &self.post_address
+    }
+
+    #[allow(dead_code)]
+    pub fn get_parent_comment(&self) -> Option<&CommentAddress<C>> {
+        self.parent_comment.as_ref()
+    }
+
+    #[allow(dead_code)]
+    pub fn get_content(&self) -> &str {
+        &self.content
+    }
+
+    #[allow(dead_code)]
+    pub fn get_depth(&self) -> u32 {
+        self.depth
+    }
+
+    #[allow(dead_code)]
+    pub fn get_replies(&self) -> &Vec<CommentAddress<C>> {
+        &self.replies
+    }
+
+    #[allow(dead_code)]
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::StateMapAccessor;
+
+    use super::MAX_COMMENT_DEPTH;
+    use crate::address::{CommentAddress, PostAddress};
+    use crate::test_utils::{error_code, last_event_key, CallResult, Fixture, Member, C};
+
+    fn reply(f: &mut Fixture, author: &Member, post: &PostAddress<C>, parent: Option<&CommentAddress<C>>) -> CallResult {
+        f.reddit
+            .create_new_comment(post.clone(), parent.cloned(), "content", &author.0, &mut f.working_set)
+    }
+
+    /// Comments on `post` and returns the address of the new comment
+    fn comment(f: &mut Fixture, author: &Member, post: &PostAddress<C>, parent: Option<&CommentAddress<C>>) -> CommentAddress<C> {
+        reply(f, author, post, parent).unwrap();
+        let siblings = match parent {
+            Some(parent) => f.reddit.comment_collections.get(parent, &mut f.working_set).unwrap().get_replies().clone(),
+            None => f.reddit.post_comment_collections.get(post, &mut f.working_set).unwrap(),
+        };
+        siblings.last().unwrap().clone()
+    }
+
+    #[test]
+    fn replies_are_returned_as_a_tree() {
+        let mut f = Fixture::new();
+        let (owner, user) = (f.owner.clone(), f.user("user"));
+        let post = f.post(&owner);
+
+        let first = comment(&mut f, &user, &post, None);
+        let second = comment(&mut f, &owner, &post, None);
+        let answer = comment(&mut f, &owner, &post, Some(&first));
+        comment(&mut f, &user, &post, Some(&answer));
+        assert_ne!(first, second);
+
+        let answer_response = f.reddit.get_comment(answer.clone(), &mut f.working_set).unwrap();
+        assert_eq!(answer_response.parent_comment, Some(first.clone()));
+        assert_eq!(answer_response.depth, 1);
+
+        let tree = f.reddit.get_comments_for_post(post.clone(), None, &mut f.working_set).unwrap().comments;
+        let top_level: Vec<_> = tree.iter().map(|node| node.comment.comment_address.clone()).collect();
+        assert_eq!(top_level, vec![first, second]);
+        assert_eq!(tree[0].replies[0].comment.comment_address, answer);
+        assert_eq!(tree[0].replies[0].replies.len(), 1);
+
+        // Replies below `max_depth` are only counted
+        let shallow = f.reddit.get_comments_for_post(post, Some(1), &mut f.working_set).unwrap().comments;
+        assert!(shallow[0].replies[0].replies.is_empty());
+        assert_eq!(shallow[0].replies[0].more_replies, 1);
+    }
+
+    #[test]
+    fn only_the_author_edits_a_comment() {
+        let mut f = Fixture::new();
+        let (owner, user) = (f.owner.clone(), f.user("user"));
+        let post = f.post(&owner);
+        let address = comment(&mut f, &user, &post, None);
+
+        let edited = f.reddit.edit_comment(address.clone(), "hijacked", &owner.0, &mut f.working_set);
+        assert_eq!(error_code(edited), "NOT_AUTHOR");
+
+        f.reddit.edit_comment(address.clone(), "edited", &user.0, &mut f.working_set).unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "CommentEdited");
+        assert_eq!(f.reddit.get_comment(address, &mut f.working_set).unwrap().content, "edited");
+    }
+
+    #[test]
+    fn deleted_comments_keep_their_replies() {
+        let mut f = Fixture::new();
+        let (owner, user, stranger) = (f.owner.clone(), f.user("user"), f.user("stranger"));
+        let post = f.post(&owner);
+        let address = comment(&mut f, &user, &post, None);
+        comment(&mut f, &owner, &post, Some(&address));
+
+        let deleted = f.reddit.delete_comment(address.clone(), &stranger.0, &mut f.working_set);
+        assert_eq!(error_code(deleted), "NOT_AUTHOR_OR_MODERATOR");
+
+        // The owner moderates the subreddit with every permission
+        f.reddit.delete_comment(address.clone(), &owner.0, &mut f.working_set).unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "CommentDeleted");
+
+        let tree = f.reddit.get_comments_for_post(post.clone(), None, &mut f.working_set).unwrap().comments;
+        assert!(tree[0].comment.deleted);
+        assert_eq!(tree[0].comment.content, "");
+        assert_eq!(tree[0].replies.len(), 1);
+
+        let edited = f.reddit.edit_comment(address.clone(), "back", &user.0, &mut f.working_set);
+        assert_eq!(error_code(edited), "COMMENT_DELETED");
+        assert_eq!(error_code(reply(&mut f, &user, &post, Some(&address))), "COMMENT_DELETED");
+        let again = f.reddit.delete_comment(address, &user.0, &mut f.working_set);
+        assert_eq!(error_code(again), "COMMENT_DELETED");
+    }
+
+    #[test]
+    fn rejects_parents_of_other_posts_and_deep_threads() {
+        let mut f = Fixture::new();
+        let owner = f.owner.clone();
+        let (post, other_post) = (f.post(&owner), f.post(&owner));
+        let other = comment(&mut f, &owner, &other_post, None);
+        assert_eq!(error_code(reply(&mut f, &owner, &post, Some(&other))), "COMMENT_NOT_IN_POST");
+
+        let mut parent = comment(&mut f, &owner, &post, None);
+        for _ in 0..MAX_COMMENT_DEPTH {
+            parent = comment(&mut f, &owner, &post, Some(&parent));
+        }
+        assert_eq!(f.reddit.get_comment(parent.clone(), &mut f.working_set).unwrap().depth, MAX_COMMENT_DEPTH);
+        assert_eq!(error_code(reply(&mut f, &owner, &post, Some(&parent))), "COMMENT_TOO_DEEP");
+    }
+
+    #[test]
+    fn missing_parents_and_posts_are_rejected() {
+        let mut f = Fixture::new();
+        let owner = f.owner.clone();
+        let post = f.post(&owner);
+        let missing = crate::utils::get_comment_address::<C>(b"nobody", post.as_ref(), None, 0);
+        assert_eq!(error_code(reply(&mut f, &owner, &post, Some(&missing))), "COMMENT_NOT_FOUND");
+
+        let missing_post = crate::utils::get_post_address::<C>(b"nobody", f.sub.as_ref(), 0);
+        assert_eq!(error_code(reply(&mut f, &owner, &missing_post, None)), "POST_NOT_FOUND");
+    }
+}
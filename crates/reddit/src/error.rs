@@ -0,0 +1,377 @@
+use std::fmt;
+
+
+/// Every failure of a reddit call or query. The `Display` output starts with the stable
+/// [`RedditError::code`] so revert reasons in receipts and logs can be matched by clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedditError {
+    // Duplicates
+    UserAlreadyExists { username: String },
+    SenderAlreadyRegistered { sender: String, user_address: String },
+    SubredditAlreadyExists { subname: String },
+    AppealAlreadyPending { sub: String },
+    AlreadySubscribed { sub: String },
+
+    // Missing entities
+    SenderNotRegistered { sender: String },
+    UserNotFound { user: String },
+    SubredditNotFound { sub: String },
+    PostNotFound { post: String },
+    CommentNotFound { comment: String },
+    BanNotFound { sub: String, user: String },
+    AppealNotFound { sub: String, user: String },
+    NotSubscribed { sub: String },
+
+    // Permission failures
+    NotUserOwner { user_address: String },
+    NotAuthor { address: String },
+    NotAuthorOrModerator { address: String },
+    NotModerator { sub: String },
+    NotSubredditOwner { sub: String },
+    MissingPermission { sub: String, permission: String },
+    UserBanned { sub: String },
+    UserMuted { sub: String },
+    CannotBanModerator { sub: String, user: String },
+    NotSeniorModerator { sub: String, moderator: String },
+    OwnerCannotLeave { sub: String },
+    AlreadyModerator { sub: String, user: String },
+    TargetNotModerator { sub: String, user: String },
+    RateLimited { action: String, max_actions: u32, window_slots: u64 },
+
+    // Validation failures
+    InvalidName { name: String, reason: String },
+    NameReserved { name: String },
+    SubredditArchived { sub: String },
+    PostNotActive { post: String, status: String },
+    CommentDeleted { comment: String },
+    CommentNotInPost { comment: String, post: String },
+    CommentTooDeep { max_depth: u32 },
+    BatchTooLarge { len: usize, max: usize },
+    InvalidPageSize { limit: usize, max: usize },
+    InvalidBanExpiry { until_slot: u64, slot_height: u64 },
+}
+
+impl RedditError {
+    /// Machine readable reason, never changes once released
+    pub fn code(&self) -> &'static str {
+        match self {
+            RedditError::UserAlreadyExists { .. } => "USER_ALREADY_EXISTS",
+            RedditError::SenderAlreadyRegistered { .. } => "SENDER_ALREADY_REGISTERED",
+            RedditError::SubredditAlreadyExists { .. } => "SUBREDDIT_ALREADY_EXISTS",
+            RedditError::SenderNotRegistered { .. } => "SENDER_NOT_REGISTERED",
+            RedditError::UserNotFound { .. } => "USER_NOT_FOUND",
+            RedditError::SubredditNotFound { .. } => "SUBREDDIT_NOT_FOUND",
+            RedditError::PostNotFound { .. } => "POST_NOT_FOUND",
+            RedditError::CommentNotFound { .. } => "COMMENT_NOT_FOUND",
+            RedditError::NotUserOwner { .. } => "NOT_USER_OWNER",
+            RedditError::NotAuthor { .. } => "NOT_AUTHOR",
+            RedditError::NotAuthorOrModerator { .. } => "NOT_AUTHOR_OR_MODERATOR",
+            RedditError::NotModerator { .. } => "NOT_MODERATOR",
+            RedditError::NotSubredditOwner { .. } => "NOT_SUBREDDIT_OWNER",
+            RedditError::MissingPermission { .. } => "MISSING_PERMISSION",
+            RedditError::UserBanned { .. } => "USER_BANNED",
+            RedditError::UserMuted { .. } => "USER_MUTED",
+            RedditError::CannotBanModerator { .. } => "CANNOT_BAN_MODERATOR",
+            RedditError::BanNotFound { .. } => "BAN_NOT_FOUND",
+            RedditError::AppealNotFound { .. } => "APPEAL_NOT_FOUND",
+            RedditError::AppealAlreadyPending { .. } => "APPEAL_ALREADY_PENDING",
+            RedditError::AlreadySubscribed { .. } => "ALREADY_SUBSCRIBED",
+            RedditError::NotSubscribed { .. } => "NOT_SUBSCRIBED",
+            RedditError::NotSeniorModerator { .. } => "NOT_SENIOR_MODERATOR",
+            RedditError::OwnerCannotLeave { .. } => "OWNER_CANNOT_LEAVE",
+            RedditError::AlreadyModerator { .. } => "ALREADY_MODERATOR",
+            RedditError::TargetNotModerator { .. } => "TARGET_NOT_MODERATOR",
+            RedditError::RateLimited { .. } => "RATE_LIMITED",
+            RedditError::InvalidName { .. } => "INVALID_NAME",
+            RedditError::NameReserved { .. } => "NAME_RESERVED",
+            RedditError::SubredditArchived { .. } => "SUBREDDIT_ARCHIVED",
+            RedditError::PostNotActive { .. } => "POST_NOT_ACTIVE",
+            RedditError::CommentDeleted { .. } => "COMMENT_DELETED",
+            RedditError::CommentNotInPost { .. } => "COMMENT_NOT_IN_POST",
+            RedditError::CommentTooDeep { .. } => "COMMENT_TOO_DEEP",
+            RedditError::BatchTooLarge { .. } => "BATCH_TOO_LARGE",
+            RedditError::InvalidPageSize { .. } => "INVALID_PAGE_SIZE",
+            RedditError::InvalidBanExpiry { .. } => "INVALID_BAN_EXPIRY",
+        }
+    }
+
+    /// JSON-RPC error code, grouped by kind:
+    /// 1xxx duplicates, 2xxx missing entities, 3xxx permissions, 4xxx validation.
+    /// Arms are kept in code order, a new error takes the next free code of its range
+    pub fn rpc_code(&self) -> i32 {
+        match self {
+            RedditError::UserAlreadyExists { .. } => 1001,
+            RedditError::SenderAlreadyRegistered { .. } => 1002,
+            RedditError::SubredditAlreadyExists { .. } => 1003,
+            RedditError::AlreadyModerator { .. } => 1004,
+            RedditError::AppealAlreadyPending { .. } => 1005,
+            RedditError::AlreadySubscribed { .. } => 1006,
+            RedditError::SenderNotRegistered { .. } => 2001,
+            RedditError::UserNotFound { .. } => 2002,
+            RedditError::SubredditNotFound { .. } => 2003,
+            RedditError::PostNotFound { .. } => 2004,
+            RedditError::CommentNotFound { .. } => 2005,
+            RedditError::TargetNotModerator { .. } => 2006,
+            RedditError::BanNotFound { .. } => 2007,
+            RedditError::AppealNotFound { .. } => 2008,
+            RedditError::NotSubscribed { .. } => 2009,
+            RedditError::NotUserOwner { .. } => 3001,
+            RedditError::NotAuthor { .. } => 3002,
+            RedditError::NotAuthorOrModerator { .. } => 3003,
+            RedditError::NotModerator { .. } => 3004,
+            RedditError::NotSubredditOwner { .. } => 3005,
+            RedditError::NotSeniorModerator { .. } => 3006,
+            RedditError::MissingPermission { .. } => 3007,
+            RedditError::UserBanned { .. } => 3008,
+            RedditError::UserMuted { .. } => 3009,
+            RedditError::CannotBanModerator { .. } => 3010,
+            RedditError::RateLimited { .. } => 3011,
+            RedditError::OwnerCannotLeave { .. } => 3012,
+            RedditError::InvalidName { .. } => 4001,
+            RedditError::NameReserved { .. } => 4002,
+            RedditError::SubredditArchived { .. } => 4003,
+            RedditError::CommentDeleted { .. } => 4004,
+            RedditError::CommentNotInPost { .. } => 4005,
+            RedditError::CommentTooDeep { .. } => 4006,
+            RedditError::BatchTooLarge { .. } => 4007,
+            RedditError::PostNotActive { .. } => 4008,
+            // 4009 was OwnerCannotLeave before it moved to the permission range, not reused
+            RedditError::InvalidPageSize { .. } => 4010,
+            RedditError::InvalidBanExpiry { .. } => 4011,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            RedditError::UserAlreadyExists { username } => {
+                format!("User with username={} already exists", username)
+            }
+            RedditError::SenderAlreadyRegistered { sender, user_address } => {
+                format!("Sender={} already owns user with address={}", sender, user_address)
+            }
+            RedditError::SubredditAlreadyExists { subname } => {
+                format!("Subreddit with subname={} already exists", subname)
+            }
+            RedditError::SenderNotRegistered { sender } => {
+                format!("Sender={} has no registered user", sender)
+            }
+            RedditError::UserNotFound { user } => format!("User={} does not exist", user),
+            RedditError::SubredditNotFound { sub } => {
+                format!("Subreddit with address={} does not exist", sub)
+            }
+            RedditError::PostNotFound { post } => format!("Post with address={} does not exist", post),
+            RedditError::CommentNotFound { comment } => {
+                format!("Comment with address={} does not exist", comment)
+            }
+            RedditError::NotUserOwner { user_address } => {
+                format!("User with address={} is not owned by the sender", user_address)
+            }
+            RedditError::NotAuthor { address } => {
+                format!("Only the author can modify address={}", address)
+            }
+            RedditError::NotAuthorOrModerator { address } => {
+                format!("Only the author or a moderator can modify address={}", address)
+            }
+            RedditError::NotModerator { sub } => {
+                format!("Sender is not a moderator of subreddit with address={}", sub)
+            }
+            RedditError::NotSubredditOwner { sub } => {
+                format!("Sender is not the owner of subreddit with address={}", sub)
+            }
+            RedditError::MissingPermission { sub, permission } => {
+                format!("Sender lacks permission={} in subreddit with address={}", permission, sub)
+            }
+            RedditError::UserBanned { sub } => {
+                format!("Sender is banned from subreddit with address={}", sub)
+            }
+            RedditError::UserMuted { sub } => {
+                format!("Sender is muted in subreddit with address={}", sub)
+            }
+            RedditError::CannotBanModerator { sub, user } => {
+                format!("User={} is a moderator of subreddit={} and cannot be banned", user, sub)
+            }
+            RedditError::BanNotFound { sub, user } => {
+                format!("User={} has no active ban in subreddit={}", user, sub)
+            }
+            RedditError::AppealNotFound { sub, user } => {
+                format!("User={} has no pending appeal in subreddit={}", user, sub)
+            }
+            RedditError::AppealAlreadyPending { sub } => {
+                format!("Sender already has a pending appeal in subreddit with address={}", sub)
+            }
+            RedditError::AlreadySubscribed { sub } => {
+                format!("Sender is already subscribed to subreddit with address={}", sub)
+            }
+            RedditError::NotSubscribed { sub } => {
+                format!("Sender is not subscribed to subreddit with address={}", sub)
+            }
+            RedditError::NotSeniorModerator { sub, moderator } => {
+                format!("Moderator={} of subreddit={} is not junior to the sender", moderator, sub)
+            }
+            RedditError::OwnerCannotLeave { sub } => {
+                format!("Owner of subreddit with address={} must transfer ownership before leaving", sub)
+            }
+            RedditError::AlreadyModerator { sub, user } => {
+                format!("User={} is already a moderator of subreddit={}", user, sub)
+            }
+            RedditError::TargetNotModerator { sub, user } => {
+                format!("User={} is not a moderator of subreddit={}", user, sub)
+            }
+            RedditError::RateLimited { action, max_actions, window_slots } => format!(
+                "Quota for {} exceeded, at most {} per {} slots",
+                action, max_actions, window_slots
+            ),
+            RedditError::InvalidName { name, reason } => format!("Name={} is invalid: {}", name, reason),
+            RedditError::NameReserved { name } => format!("Name={} is reserved", name),
+            RedditError::SubredditArchived { sub } => {
+                format!("Subreddit with address={} is archived", sub)
+            }
+            RedditError::PostNotActive { post, status } => {
+                format!("Post with address={} is {}", post, status)
+            }
+            RedditError::CommentDeleted { comment } => {
+                format!("Comment with address={} is deleted", comment)
+            }
+            RedditError::CommentNotInPost { comment, post } => {
+                format!("Comment with address={} does not belong to post={}", comment, post)
+            }
+            RedditError::CommentTooDeep { max_depth } => {
+                format!("Comment nesting is limited to depth={}", max_depth)
+            }
+            RedditError::BatchTooLarge { len, max } => {
+                format!("Batch of {} addresses exceeds the limit of {}", len, max)
+            }
+            RedditError::InvalidPageSize { limit, max } => {
+                format!("Page size={} must be between 1 and {}", limit, max)
+            }
+            RedditError::InvalidBanExpiry { until_slot, slot_height } => {
+                format!("Ban ending at slot={} must end after the current slot={}", until_slot, slot_height)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RedditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for RedditError {}
+
+#[cfg(feature = "native")]
+impl From<RedditError> for jsonrpsee::core::Error {
+    fn from(e: RedditError) -> Self {
+        jsonrpsee::types::ErrorObjectOwned::owned(e.rpc_code(), e.message(), Some(e.code())).into()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::RedditError;
+
+    fn s() -> String {
+        String::new()
+    }
+
+    /// One error of every kind
+    fn all_errors() -> Vec<RedditError> {
+        vec![
+            RedditError::UserAlreadyExists { username: s() },
+            RedditError::SenderAlreadyRegistered { sender: s(), user_address: s() },
+            RedditError::SubredditAlreadyExists { subname: s() },
+            RedditError::AppealAlreadyPending { sub: s() },
+            RedditError::AlreadySubscribed { sub: s() },
+            RedditError::SenderNotRegistered { sender: s() },
+            RedditError::UserNotFound { user: s() },
+            RedditError::SubredditNotFound { sub: s() },
+            RedditError::PostNotFound { post: s() },
+            RedditError::CommentNotFound { comment: s() },
+            RedditError::BanNotFound { sub: s(), user: s() },
+            RedditError::AppealNotFound { sub: s(), user: s() },
+            RedditError::NotSubscribed { sub: s() },
+            RedditError::NotUserOwner { user_address: s() },
+            RedditError::NotAuthor { address: s() },
+            RedditError::NotAuthorOrModerator { address: s() },
+            RedditError::NotModerator { sub: s() },
+            RedditError::NotSubredditOwner { sub: s() },
+            RedditError::MissingPermission { sub: s(), permission: s() },
+            RedditError::UserBanned { sub: s() },
+            RedditError::UserMuted { sub: s() },
+            RedditError::CannotBanModerator { sub: s(), user: s() },
+            RedditError::NotSeniorModerator { sub: s(), moderator: s() },
+            RedditError::OwnerCannotLeave { sub: s() },
+            RedditError::AlreadyModerator { sub: s(), user: s() },
+            RedditError::TargetNotModerator { sub: s(), user: s() },
+            RedditError::RateLimited { action: s(), max_actions: 0, window_slots: 0 },
+            RedditError::InvalidName { name: s(), reason: s() },
+            RedditError::NameReserved { name: s() },
+            RedditError::SubredditArchived { sub: s() },
+            RedditError::PostNotActive { post: s(), status: s() },
+            RedditError::CommentDeleted { comment: s() },
+            RedditError::CommentNotInPost { comment: s(), post: s() },
+            RedditError::CommentTooDeep { max_depth: 0 },
+            RedditError::BatchTooLarge { len: 0, max: 0 },
+            RedditError::InvalidPageSize { limit: 0, max: 0 },
+            RedditError::InvalidBanExpiry { until_slot: 0, slot_height: 0 },
+        ]
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let errors = all_errors();
+        let codes: HashSet<_> = errors.iter().map(RedditError::code).collect();
+        let rpc_codes: HashSet<_> = errors.iter().map(RedditError::rpc_code).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(rpc_codes.len(), errors.len());
+    }
+
+    #[test]
+    fn rpc_codes_match_the_kind_of_error() {
+        for error in all_errors() {
+            let range = match error {
+                RedditError::UserAlreadyExists { .. }
+                | RedditError::SenderAlreadyRegistered { .. }
+                | RedditError::SubredditAlreadyExists { .. }
+                | RedditError::AppealAlreadyPending { .. }
+                | RedditError::AlreadySubscribed { .. }
+                | RedditError::AlreadyModerator { .. } => 1000..2000,
+                RedditError::SenderNotRegistered { .. }
+                | RedditError::UserNotFound { .. }
+                | RedditError::SubredditNotFound { .. }
+                | RedditError::PostNotFound { .. }
+                | RedditError::CommentNotFound { .. }
+                | RedditError::BanNotFound { .. }
+                | RedditError::AppealNotFound { .. }
+                | RedditError::NotSubscribed { .. }
+                | RedditError::TargetNotModerator { .. } => 2000..3000,
+                RedditError::NotUserOwner { .. }
+                | RedditError::NotAuthor { .. }
+                | RedditError::NotAuthorOrModerator { .. }
+                | RedditError::NotModerator { .. }
+                | RedditError::NotSubredditOwner { .. }
+                | RedditError::MissingPermission { .. }
+                | RedditError::UserBanned { .. }
+                | RedditError::UserMuted { .. }
+                | RedditError::CannotBanModerator { .. }
+                | RedditError::NotSeniorModerator { .. }
+                | RedditError::OwnerCannotLeave { .. }
+                | RedditError::RateLimited { .. } => 3000..4000,
+                RedditError::InvalidName { .. }
+                | RedditError::NameReserved { .. }
+                | RedditError::SubredditArchived { .. }
+                | RedditError::PostNotActive { .. }
+                | RedditError::CommentDeleted { .. }
+                | RedditError::CommentNotInPost { .. }
+                | RedditError::CommentTooDeep { .. }
+                | RedditError::BatchTooLarge { .. }
+                | RedditError::InvalidPageSize { .. }
+                | RedditError::InvalidBanExpiry { .. } => 4000..5000,
+            };
+            assert!(range.contains(&error.rpc_code()), "{} has rpc code {}", error.code(), error.rpc_code());
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use sov_modules_api::{Context, StateValueAccessor, WorkingSet};
+
+use crate::{address::{CommentAddress, PostAddress, SubAddress, UserAddress}, moderator::ModPermission, vote::VoteDirection, Reddit};
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Clone)]
+/// Emitted by every state changing reddit call, one event per change
+pub enum RedditEvent<C: Context> {
+    UserCreated {
+        user_address: UserAddress<C>,
+        username: String,
+        owner: C::Address,
+    },
+    SubredditCreated {
+        sub_address: SubAddress<C>,
+        subname: String,
+        description: String,
+        creator: UserAddress<C>,
+    },
+    SubredditSettingsUpdated {
+        sub_address: SubAddress<C>,
+    },
+    SubredditArchived {
+        sub_address: SubAddress<C>,
+        archived_by: UserAddress<C>,
+    },
+    ModeratorAdded {
+        sub_address: SubAddress<C>,
+        moderator: UserAddress<C>,
+        permissions: Vec<ModPermission>,
+        added_by: UserAddress<C>,
+    },
+    ModeratorPermissionsUpdated {
+        sub_address: SubAddress<C>,
+        moderator: UserAddress<C>,
+        permissions: Vec<ModPermission>,
+        updated_by: UserAddress<C>,
+    },
+    ModeratorRemoved {
+        sub_address: SubAddress<C>,
+        moderator: UserAddress<C>,
+        removed_by: UserAddress<C>,
+    },
+    OwnershipTransferred {
+        sub_address: SubAddress<C>,
+        previous_owner: UserAddress<C>,
+        new_owner: UserAddress<C>,
+    },
+    UserBanned {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        banned_by: UserAddress<C>,
+        until_slot: Option<u64>,
+        reason: String,
+    },
+    UserUnbanned {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        unbanned_by: UserAddress<C>,
+    },
+    UserMuted {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        muted_by: UserAddress<C>,
+        until_slot: Option<u64>,
+    },
+    BanAppealed {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+    },
+    AppealDismissed {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+        dismissed_by: UserAddress<C>,
+    },
+    Subscribed {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+    },
+    Unsubscribed {
+        sub_address: SubAddress<C>,
+        user_address: UserAddress<C>,
+    },
+    PostCreated {
+        post_address: PostAddress<C>,
+        sub_address: SubAddress<C>,
+        author: UserAddress<C>,
+        title: String,
+        flair: String,
+        content: String,
+    },
+    /// Carries the content and flair after the edit
+    PostEdited {
+        post_address: PostAddress<C>,
+        content: String,
+        flair: String,
+    },
+    PostDeleted {
+        post_address: PostAddress<C>,
+        deleted_by: UserAddress<C>,
+    },
+    /// Emitted by the end slot hook once the archive period of the post is over
+    PostArchived {
+        post_address: PostAddress<C>,
+    },
+    CommentCreated {
+        comment_address: CommentAddress<C>,
+        post_address: PostAddress<C>,
+        parent_comment: Option<CommentAddress<C>>,
+        author: UserAddress<C>,
+        content: String,
+    },
+    CommentEdited {
+        comment_address: CommentAddress<C>,
+        content: String,
+    },
+    CommentDeleted {
+        comment_address: CommentAddress<C>,
+    },
+    PostVoted {
+        post_address: PostAddress<C>,
+        voter: UserAddress<C>,
+        direction: VoteDirection,
+        score: i64,
+    },
+}
+
+impl<C: Context> RedditEvent<C> {
+    /// Event key stored in the transaction receipt
+    pub fn key(&self) -> &'static str {
+        match self {
+            RedditEvent::UserCreated { .. } => "UserCreated",
+            RedditEvent::SubredditCreated { .. } => "SubredditCreated",
+            RedditEvent::SubredditSettingsUpdated { .. } => "SubredditSettingsUpdated",
+            RedditEvent::SubredditArchived { .. } => "SubredditArchived",
+            RedditEvent::ModeratorAdded { .. } => "ModeratorAdded",
+            RedditEvent::ModeratorPermissionsUpdated { .. } => "ModeratorPermissionsUpdated",
+            RedditEvent::ModeratorRemoved { .. } => "ModeratorRemoved",
+            RedditEvent::OwnershipTransferred { .. } => "OwnershipTransferred",
+            RedditEvent::UserBanned { .. } => "UserBanned",
+            RedditEvent::UserUnbanned { .. } => "UserUnbanned",
+            RedditEvent::UserMuted { .. } => "UserMuted",
+            RedditEvent::BanAppealed { .. } => "BanAppealed",
+            RedditEvent::AppealDismissed { .. } => "AppealDismissed",
+            RedditEvent::Subscribed { .. } => "Subscribed",
+            RedditEvent::Unsubscribed { .. } => "Unsubscribed",
+            RedditEvent::PostCreated { .. } => "PostCreated",
+            RedditEvent::PostEdited { .. } => "PostEdited",
+            RedditEvent::PostDeleted { .. } => "PostDeleted",
+            RedditEvent::PostArchived { .. } => "PostArchived",
+            RedditEvent::CommentCreated { .. } => "CommentCreated",
+            RedditEvent::CommentEdited { .. } => "CommentEdited",
+            RedditEvent::CommentDeleted { .. } => "CommentDeleted",
+            RedditEvent::PostVoted { .. } => "PostVoted",
+        }
+    }
+}
+
+impl<C: Context> Reddit<C> {
+    /// Adds the event to the receipt of the current transaction, the value is the JSON encoded event.
+    /// Native nodes also buffer the change for the offchain sink, it is published once the slot is committed
+    pub(crate) fn emit_event(&self, event: RedditEvent<C>, working_set: &mut WorkingSet<C>) {
+        // Read outside the native block, the zk execution has to touch the same keys
+        let slot_height = self.current_slot_height.get(working_set).unwrap_or_default();
+
+        #[cfg(feature = "native")]
+        {
+            // Only native nodes number the changes, the counter is reverted with the transaction
+            // like the rest of its writes but never enters the state root or the zk witness
+            let sequence = {
+                let mut accessory = working_set.accessory_state();
+                let sequence = self.change_sequence.get(&mut accessory).unwrap_or_default();
+                self.change_sequence.set(&(sequence + 1), &mut accessory);
+                sequence
+            };
+
+            crate::offchain::publish_state(crate::offchain::RedditChangeEnvelope::new(
+                sequence,
+                slot_height,
+                crate::offchain::RedditStateChanges::from_event(&event),
+            ));
+            working_set.add_event(
+                event.key(),
+                &serde_json::to_string(&event).expect("RedditEvent serialization cannot fail"),
+            );
+        }
+
+        #[cfg(not(feature = "native"))]
+        let _ = (event, slot_height);
+    }
+}
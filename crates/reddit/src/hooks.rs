@@ -1,6 +1,9 @@
-use sov_modules_api::hooks::TxHooks;
+use sov_modules_api::hooks::{FinalizeHook, SlotHooks, TxHooks};
 use sov_modules_api::transaction::Transaction;
-use sov_modules_api::{Context, PublicKey, Spec, WorkingSet};
+use sov_modules_api::{AccessoryWorkingSet, BlockHeaderTrait, Context, DaSpec, PublicKey, Spec, StateMapAccessor, StateValueAccessor, WorkingSet};
+use sov_state::Storage;
+use crate::event::RedditEvent;
+use crate::post::PostStatus;
 use crate::Reddit;
 
 
@@ -10,7 +13,7 @@ impl<C: Context> TxHooks for Reddit<C> {
      type PreArg = C::PublicKey;
     type PreResult = C::Address;
 
-    
+    /// Remembers the hash of the transaction for the offchain change envelopes
     fn pre_dispatch_tx_hook(
         &self,
         tx: &Transaction<C>,
@@ -19,6 +22,13 @@ impl<C: Context> TxHooks for Reddit<C> {
     ) -> anyhow::Result<<Self::Context as Spec>::Address> {
         let pub_key = tx.pub_key();
 
+        #[cfg(feature = "native")]
+        crate::offchain::set_current_tx_hash(
+            borsh::BorshSerialize::try_to_vec(tx)
+                .ok()
+                .map(|raw| format!("0x{}", hex::encode(<sha2::Sha256 as sha2::Digest>::digest(raw)))),
+        );
+
         Ok(pub_key.to_address())
     }
 
@@ -28,9 +38,89 @@ impl<C: Context> TxHooks for Reddit<C> {
         _ctx: &C,
         working_set: &mut WorkingSet<C>,
     ) -> anyhow::Result<()> {
+        #[cfg(feature = "native")]
+        crate::offchain::set_current_tx_hash(None);
 
         Ok(())
     }
     
 
 }
+
+
+impl<C: Context, Da: DaSpec> SlotHooks<Da> for Reddit<C> {
+    type Context = C;
+
+    fn begin_slot_hook(
+        &self,
+        slot_header: &Da::BlockHeader,
+        _validity_condition: &Da::ValidityCondition,
+        _pre_state_root: &<<Self::Context as Spec>::Storage as Storage>::Root,
+        working_set: &mut WorkingSet<C>,
+    ) {
+        self.current_slot_height.set(&slot_header.height(), working_set);
+        self.current_slot_timestamp.set(&slot_header.time().secs(), working_set);
+
+        #[cfg(feature = "native")]
+        crate::offchain::begin_slot(
+            slot_header.height(),
+            format!("0x{}", hex::encode(Into::<[u8; 32]>::into(slot_header.hash()))),
+        );
+    }
+
+    /// Archives every post whose archive slot is the one that just ended
+    fn end_slot_hook(&self, working_set: &mut WorkingSet<C>) {
+        self.archive_expired_posts(working_set);
+
+        #[cfg(feature = "native")]
+        {
+            let next_sequence = self.change_sequence.get(&mut working_set.accessory_state()).unwrap_or_default();
+            crate::offchain::end_slot(next_sequence);
+        }
+    }
+}
+
+
+impl<C: Context, Da: DaSpec> FinalizeHook<Da> for Reddit<C> {
+    type Context = C;
+
+    /// Runs after every executed slot. Feeds live in accessory state, so ranking never touches the state root.
+    /// Native nodes publish the offchain changes of the slot from here, after its state can no longer be reverted
+    fn finalize_hook(
+        &self,
+        _root_hash: &<<Self::Context as Spec>::Storage as Storage>::Root,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) {
+        self.apply_rank_updates(accessory_working_set);
+
+        #[cfg(feature = "native")]
+        crate::offchain::commit_slot();
+    }
+}
+
+
+impl<C: Context> Reddit<C> {
+    fn archive_expired_posts(&self, working_set: &mut WorkingSet<C>) {
+        let Some(slot_height) = self.current_slot_height.get(working_set) else {
+            return;
+        };
+
+        let Some(expired) = self.archive_queue.get(&slot_height, working_set) else {
+            return;
+        };
+
+        for post_address in expired {
+            if let Some(mut post) = self.post_collections.get(&post_address, working_set) {
+                // Deleted posts stay tombstones
+                if post.get_post_status() == PostStatus::ACTIVE {
+                    post.mark_archived();
+                    self.post_collections.set(&post_address, &post, working_set);
+                    self.queue_rank_update(&post, working_set);
+                    self.emit_event(RedditEvent::PostArchived { post_address }, working_set);
+                }
+            }
+        }
+
+        self.archive_queue.remove(&slot_height, working_set);
+    }
+}
@@ -0,0 +1,913 @@
+//! Relational copy of the reddit state in SQLite, fed with the offchain change envelopes.
+//! Runs inside the node as the `sqlite` offchain sink, but [`Indexer`] only needs envelopes
+//! and can be driven from any other source, e.g. a webhook receiver or a JSONL file.
+//!
+//! `REVERTED` envelopes arrive in a run, newest first. The run rolls the database back once, to the
+//! slot before the oldest reverted change. Triggers record how to undo every row each change writes,
+//! the rollback runs those statements backwards for the reverted changes only. Undo statements are
+//! kept as long as the offchain journal keeps slots, a deeper rollback rebuilds the tables instead
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::journal::MAX_JOURNAL_SLOTS;
+use crate::moderator::ModPermission;
+use crate::offchain::{ChangeType, ChangeWriter, RedditChangeEnvelope};
+
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS users (
+    address TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    karma INTEGER NOT NULL DEFAULT 0,
+    created_slot INTEGER NOT NULL,
+    updated_slot INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS users_username ON users (lower(username));
+
+CREATE TABLE IF NOT EXISTS subreddits (
+    address TEXT PRIMARY KEY,
+    subname TEXT NOT NULL,
+    description TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    archived INTEGER NOT NULL DEFAULT 0,
+    created_slot INTEGER NOT NULL,
+    updated_slot INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS posts (
+    address TEXT PRIMARY KEY,
+    sub_address TEXT NOT NULL,
+    author TEXT NOT NULL,
+    title TEXT NOT NULL,
+    flair TEXT NOT NULL,
+    content TEXT NOT NULL,
+    status TEXT NOT NULL,
+    score INTEGER NOT NULL DEFAULT 0,
+    upvotes INTEGER NOT NULL DEFAULT 0,
+    downvotes INTEGER NOT NULL DEFAULT 0,
+    created_slot INTEGER NOT NULL,
+    updated_slot INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS posts_sub ON posts (sub_address, created_slot);
+CREATE INDEX IF NOT EXISTS posts_author ON posts (author, created_slot);
+
+CREATE TABLE IF NOT EXISTS comments (
+    address TEXT PRIMARY KEY,
+    post_address TEXT NOT NULL,
+    parent_address TEXT,
+    author TEXT NOT NULL,
+    content TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    created_slot INTEGER NOT NULL,
+    updated_slot INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS comments_post ON comments (post_address);
+
+CREATE TABLE IF NOT EXISTS votes (
+    post_address TEXT NOT NULL,
+    voter TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    PRIMARY KEY (post_address, voter)
+);
+
+-- Moderators of each subreddit, `permissions` is the JSON array of granted permissions
+CREATE TABLE IF NOT EXISTS moderators (
+    sub_address TEXT NOT NULL,
+    moderator TEXT NOT NULL,
+    permissions TEXT NOT NULL,
+    added_by TEXT NOT NULL,
+    created_slot INTEGER NOT NULL,
+    updated_slot INTEGER NOT NULL,
+    PRIMARY KEY (sub_address, moderator)
+);
+
+-- Bans stay listed after `until_slot` until a moderator lifts them, like in the rollup state
+CREATE TABLE IF NOT EXISTS bans (
+    sub_address TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    banned_by TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    until_slot INTEGER,
+    created_slot INTEGER NOT NULL,
+    PRIMARY KEY (sub_address, user_address)
+);
+
+CREATE TABLE IF NOT EXISTS mutes (
+    sub_address TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    muted_by TEXT NOT NULL,
+    until_slot INTEGER,
+    created_slot INTEGER NOT NULL,
+    PRIMARY KEY (sub_address, user_address)
+);
+
+-- Pending ban appeals, removed when dismissed or when the ban is lifted
+CREATE TABLE IF NOT EXISTS appeals (
+    sub_address TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    created_slot INTEGER NOT NULL,
+    PRIMARY KEY (sub_address, user_address)
+);
+
+CREATE TABLE IF NOT EXISTS subscriptions (
+    sub_address TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    created_slot INTEGER NOT NULL,
+    PRIMARY KEY (sub_address, user_address)
+);
+CREATE INDEX IF NOT EXISTS subscriptions_user ON subscriptions (user_address);
+
+-- Every applied envelope, so replays are skipped and the tables can be rebuilt after a reorg
+CREATE TABLE IF NOT EXISTS applied_changes (
+    sequence INTEGER PRIMARY KEY,
+    slot_height INTEGER NOT NULL,
+    slot_hash TEXT,
+    tx_hash TEXT,
+    event TEXT NOT NULL,
+    address TEXT NOT NULL,
+    envelope TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS applied_changes_slot ON applied_changes (slot_height);
+
+-- Statements undoing the row writes of each applied change, newest last
+CREATE TABLE IF NOT EXISTS undo_log (
+    id INTEGER PRIMARY KEY,
+    sequence INTEGER NOT NULL,
+    statement TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS undo_log_sequence ON undo_log (sequence);
+
+-- Change being applied, NULL while rolling back, and the lowest change the undo log still covers
+CREATE TABLE IF NOT EXISTS undo_state (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    sequence INTEGER,
+    floor INTEGER NOT NULL
+);
+INSERT OR IGNORE INTO undo_state (id, sequence, floor)
+    SELECT 0, NULL, COALESCE(MAX(sequence) + 1, 0) FROM applied_changes;
+";
+
+/// Tables filled from the change envelopes, each with triggers feeding the undo log
+const TABLES: [&str; 10] = [
+    "users",
+    "subreddits",
+    "posts",
+    "comments",
+    "votes",
+    "moderators",
+    "bans",
+    "mutes",
+    "appeals",
+    "subscriptions",
+];
+
+
+/// Applies change envelopes to a SQLite database, each envelope in its own transaction
+pub struct Indexer {
+    conn: Connection,
+    /// Lowest slot of the `REVERTED` envelopes that were not rolled back yet
+    pending_rollback: Option<u64>,
+}
+
+impl Indexer {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        create_undo_triggers(&conn)?;
+        Ok(Indexer { conn, pending_rollback: None })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Highest sequence applied so far
+    pub fn last_sequence(&self) -> anyhow::Result<Option<u64>> {
+        let sequence: Option<i64> = self
+            .conn
+            .query_row("SELECT MAX(sequence) FROM applied_changes", [], |row| row.get(0))?;
+        Ok(sequence.map(|sequence| sequence as u64))
+    }
+
+    /// Returns `false` without touching the database when the envelope was already applied.
+    /// A `REVERTED` envelope is only collected, the rollback runs before the next other change or on [`Indexer::flush`]
+    pub fn apply(&mut self, envelope: &RedditChangeEnvelope) -> anyhow::Result<bool> {
+        if envelope.changes.change_type == ChangeType::REVERTED {
+            let slot_height = envelope.slot_height;
+            self.pending_rollback = Some(self.pending_rollback.map_or(slot_height, |pending| pending.min(slot_height)));
+            return Ok(true);
+        }
+        self.flush()?;
+
+        let tx = self.conn.transaction()?;
+
+        let seen = tx
+            .query_row(
+                "SELECT 1 FROM applied_changes WHERE sequence = ?1",
+                params![envelope.sequence as i64],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if seen.is_some() {
+            return Ok(false);
+        }
+
+        tx.execute("UPDATE undo_state SET sequence = ?1", params![envelope.sequence as i64])?;
+        let key = apply_envelope(&tx, envelope)?;
+        tx.execute("UPDATE undo_state SET sequence = NULL", [])?;
+
+        tx.execute(
+            "INSERT INTO applied_changes (sequence, slot_height, slot_hash, tx_hash, event, address, envelope)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                envelope.sequence as i64,
+                envelope.slot_height as i64,
+                envelope.slot_hash,
+                envelope.tx_hash,
+                key,
+                envelope.changes.address,
+                serde_json::to_string(envelope)?,
+            ],
+        )?;
+
+        // Reverts never reach further back than the journal keeps slots
+        let floor: i64 = tx.query_row(
+            "SELECT MIN(sequence) FROM applied_changes WHERE slot_height > ?1",
+            params![envelope.slot_height.saturating_sub(MAX_JOURNAL_SLOTS as u64) as i64],
+            |row| row.get(0),
+        )?;
+        tx.execute("DELETE FROM undo_log WHERE sequence < ?1", params![floor])?;
+        tx.execute("UPDATE undo_state SET floor = MAX(floor, ?1)", params![floor])?;
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Runs the rollback collected from `REVERTED` envelopes, returns the number of forgotten changes
+    pub fn flush(&mut self) -> anyhow::Result<usize> {
+        let Some(slot_height) = self.pending_rollback else {
+            return Ok(0);
+        };
+        let removed = self.rollback_to_slot(slot_height)?;
+        self.pending_rollback = None;
+        Ok(removed)
+    }
+
+    /// Forgets every change from `slot_height` on and undoes their writes, tables are only rebuilt
+    /// from the remaining changes when the undo log no longer covers the slot. Returns the number of forgotten changes
+    pub fn rollback_to_slot(&mut self, slot_height: u64) -> anyhow::Result<usize> {
+        let tx = self.conn.transaction()?;
+
+        let first: Option<i64> = tx.query_row(
+            "SELECT MIN(sequence) FROM applied_changes WHERE slot_height >= ?1",
+            params![slot_height as i64],
+            |row| row.get(0),
+        )?;
+        let Some(first) = first else {
+            return Ok(0);
+        };
+        let floor: i64 = tx.query_row("SELECT floor FROM undo_state", [], |row| row.get(0))?;
+
+        let removed = tx.execute("DELETE FROM applied_changes WHERE sequence >= ?1", params![first])?;
+        if first >= floor {
+            let statements: Vec<String> = {
+                let mut statement = tx.prepare("SELECT statement FROM undo_log WHERE sequence >= ?1 ORDER BY id DESC")?;
+                let rows = statement.query_map(params![first], |row| row.get(0))?;
+                rows.collect::<Result<_, _>>()?
+            };
+            for statement in statements {
+                tx.execute_batch(&statement)?;
+            }
+            tx.execute("DELETE FROM undo_log WHERE sequence >= ?1", params![first])?;
+        } else {
+            rebuild(&tx)?;
+        }
+
+        tx.commit()?;
+        Ok(removed)
+    }
+}
+
+
+/// Refills every table from `applied_changes`, the undo log starts over after the last change
+fn rebuild(tx: &Transaction) -> anyhow::Result<()> {
+    for table in TABLES {
+        tx.execute(&format!("DELETE FROM {}", table), [])?;
+    }
+
+    let remaining: Vec<String> = {
+        let mut statement = tx.prepare("SELECT envelope FROM applied_changes ORDER BY sequence")?;
+        let rows = statement.query_map([], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for envelope in remaining {
+        apply_envelope(tx, &serde_json::from_str(&envelope)?)?;
+    }
+
+    tx.execute("DELETE FROM undo_log", [])?;
+    tx.execute(
+        "UPDATE undo_state SET floor = (SELECT COALESCE(MAX(sequence) + 1, 0) FROM applied_changes)",
+        [],
+    )?;
+    Ok(())
+}
+
+
+/// Adds triggers to every table that log the inverse of each row write while `undo_state.sequence` is set.
+/// Rows are addressed by rowid, restored rows keep theirs so older undo statements still find them
+fn create_undo_triggers(conn: &Connection) -> anyhow::Result<()> {
+    for table in TABLES {
+        let columns: Vec<String> = {
+            let mut statement = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+            let rows = statement.query_map([], |row| row.get(1))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let names = columns.join(", ");
+        let values = columns
+            .iter()
+            .map(|column| format!("quote(old.{})", column))
+            .collect::<Vec<_>>()
+            .join(" || ', ' || ");
+        let assignments = columns
+            .iter()
+            .map(|column| format!("'{} = ' || quote(old.{})", column, column))
+            .collect::<Vec<_>>()
+            .join(" || ', ' || ");
+
+        conn.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {table}_undo_insert;
+             CREATE TRIGGER {table}_undo_insert AFTER INSERT ON {table}
+             WHEN (SELECT sequence FROM undo_state) IS NOT NULL BEGIN
+                INSERT INTO undo_log (sequence, statement)
+                SELECT sequence, 'DELETE FROM {table} WHERE rowid = ' || new.rowid FROM undo_state;
+             END;
+
+             DROP TRIGGER IF EXISTS {table}_undo_update;
+             CREATE TRIGGER {table}_undo_update AFTER UPDATE ON {table}
+             WHEN (SELECT sequence FROM undo_state) IS NOT NULL BEGIN
+                INSERT INTO undo_log (sequence, statement)
+                SELECT sequence, 'UPDATE {table} SET ' || {assignments} || ' WHERE rowid = ' || old.rowid FROM undo_state;
+             END;
+
+             DROP TRIGGER IF EXISTS {table}_undo_delete;
+             CREATE TRIGGER {table}_undo_delete AFTER DELETE ON {table}
+             WHEN (SELECT sequence FROM undo_state) IS NOT NULL BEGIN
+                INSERT INTO undo_log (sequence, statement)
+                SELECT sequence, 'INSERT INTO {table} (rowid, {names}) VALUES (' || old.rowid || ', ' || {values} || ')' FROM undo_state;
+             END;",
+            table = table,
+            names = names,
+            values = values,
+            assignments = assignments,
+        ))?;
+    }
+    Ok(())
+}
+
+
+/// Applies the event inside `envelope` and returns its key
+fn apply_envelope(tx: &Transaction, envelope: &RedditChangeEnvelope) -> anyhow::Result<String> {
+    let event: Value = serde_json::from_str(&envelope.changes.change)?;
+    let (key, body) = match &event {
+        Value::Object(map) if map.len() == 1 => map.iter().next().expect("checked length"),
+        _ => anyhow::bail!("Change {} is not a reddit event", envelope.sequence),
+    };
+
+    apply_event(tx, key, body, envelope.slot_height as i64)?;
+    Ok(key.clone())
+}
+
+
+/// `SubredditSettingsUpdated` does not carry the new settings, it only bumps `updated_slot` of its subreddit.
+/// Unknown events are skipped so an older indexer keeps up with a newer node
+fn apply_event(tx: &Transaction, key: &str, body: &Value, slot: i64) -> anyhow::Result<()> {
+    let field = |name: &str| -> anyhow::Result<String> {
+        match body.get(name) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(Value::Null) | None => anyhow::bail!("{} is missing {}", key, name),
+            Some(other) => Ok(other.to_string()),
+        }
+    };
+
+    match key {
+        "UserCreated" => {
+            tx.execute(
+                "INSERT OR IGNORE INTO users (address, username, owner, created_slot, updated_slot) VALUES (?1, ?2, ?3, ?4, ?4)",
+                params![field("user_address")?, field("username")?, field("owner")?, slot],
+            )?;
+        }
+        "SubredditCreated" => {
+            tx.execute(
+                "INSERT OR IGNORE INTO subreddits (address, subname, description, owner, created_slot, updated_slot) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                params![field("sub_address")?, field("subname")?, field("description")?, field("creator")?, slot],
+            )?;
+            // The creator is the first moderator, with every permission
+            tx.execute(
+                "INSERT OR IGNORE INTO moderators (sub_address, moderator, permissions, added_by, created_slot, updated_slot)
+                 VALUES (?1, ?2, ?3, ?2, ?4, ?4)",
+                params![field("sub_address")?, field("creator")?, all_permissions(), slot],
+            )?;
+        }
+        "SubredditSettingsUpdated" => touch_subreddit(tx, &field("sub_address")?, slot)?,
+        "OwnershipTransferred" => {
+            tx.execute(
+                "UPDATE subreddits SET owner = ?2, updated_slot = ?3 WHERE address = ?1",
+                params![field("sub_address")?, field("new_owner")?, slot],
+            )?;
+            tx.execute(
+                "UPDATE moderators SET permissions = ?3, updated_slot = ?4 WHERE sub_address = ?1 AND moderator = ?2",
+                params![field("sub_address")?, field("new_owner")?, all_permissions(), slot],
+            )?;
+        }
+        "ModeratorAdded" => {
+            tx.execute(
+                "INSERT INTO moderators (sub_address, moderator, permissions, added_by, created_slot, updated_slot)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+                 ON CONFLICT (sub_address, moderator) DO UPDATE SET
+                    permissions = excluded.permissions, added_by = excluded.added_by, updated_slot = excluded.updated_slot",
+                params![field("sub_address")?, field("moderator")?, field("permissions")?, field("added_by")?, slot],
+            )?;
+            touch_subreddit(tx, &field("sub_address")?, slot)?;
+        }
+        "ModeratorPermissionsUpdated" => {
+            tx.execute(
+                "UPDATE moderators SET permissions = ?3, updated_slot = ?4 WHERE sub_address = ?1 AND moderator = ?2",
+                params![field("sub_address")?, field("moderator")?, field("permissions")?, slot],
+            )?;
+            touch_subreddit(tx, &field("sub_address")?, slot)?;
+        }
+        "ModeratorRemoved" => {
+            tx.execute(
+                "DELETE FROM moderators WHERE sub_address = ?1 AND moderator = ?2",
+                params![field("sub_address")?, field("moderator")?],
+            )?;
+            touch_subreddit(tx, &field("sub_address")?, slot)?;
+        }
+        "UserBanned" => {
+            let until_slot = body.get("until_slot").and_then(Value::as_i64);
+            tx.execute(
+                "INSERT INTO bans (sub_address, user_address, banned_by, reason, until_slot, created_slot)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (sub_address, user_address) DO UPDATE SET
+                    banned_by = excluded.banned_by, reason = excluded.reason,
+                    until_slot = excluded.until_slot, created_slot = excluded.created_slot",
+                params![field("sub_address")?, field("user_address")?, field("banned_by")?, field("reason")?, until_slot, slot],
+            )?;
+        }
+        "UserUnbanned" => {
+            let (sub_address, user_address) = (field("sub_address")?, field("user_address")?);
+            tx.execute(
+                "DELETE FROM bans WHERE sub_address = ?1 AND user_address = ?2",
+                params![sub_address, user_address],
+            )?;
+            tx.execute(
+                "DELETE FROM appeals WHERE sub_address = ?1 AND user_address = ?2",
+                params![sub_address, user_address],
+            )?;
+        }
+        "UserMuted" => {
+            let until_slot = body.get("until_slot").and_then(Value::as_i64);
+            tx.execute(
+                "INSERT INTO mutes (sub_address, user_address, muted_by, until_slot, created_slot)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (sub_address, user_address) DO UPDATE SET
+                    muted_by = excluded.muted_by, until_slot = excluded.until_slot, created_slot = excluded.created_slot",
+                params![field("sub_address")?, field("user_address")?, field("muted_by")?, until_slot, slot],
+            )?;
+        }
+        "BanAppealed" => {
+            tx.execute(
+                "INSERT OR IGNORE INTO appeals (sub_address, user_address, created_slot) VALUES (?1, ?2, ?3)",
+                params![field("sub_address")?, field("user_address")?, slot],
+            )?;
+        }
+        "AppealDismissed" => {
+            tx.execute(
+                "DELETE FROM appeals WHERE sub_address = ?1 AND user_address = ?2",
+                params![field("sub_address")?, field("user_address")?],
+            )?;
+        }
+        "Subscribed" => {
+            tx.execute(
+                "INSERT OR IGNORE INTO subscriptions (sub_address, user_address, created_slot) VALUES (?1, ?2, ?3)",
+                params![field("sub_address")?, field("user_address")?, slot],
+            )?;
+        }
+        "Unsubscribed" => {
+            tx.execute(
+                "DELETE FROM subscriptions WHERE sub_address = ?1 AND user_address = ?2",
+                params![field("sub_address")?, field("user_address")?],
+            )?;
+        }
+        "SubredditArchived" => {
+            tx.execute(
+                "UPDATE subreddits SET archived = 1, updated_slot = ?2 WHERE address = ?1",
+                params![field("sub_address")?, slot],
+            )?;
+        }
+        "PostCreated" => {
+            tx.execute(
+                "INSERT OR IGNORE INTO posts (address, sub_address, author, title, flair, content, status, created_slot, updated_slot)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'ACTIVE', ?7, ?7)",
+                params![
+                    field("post_address")?,
+                    field("sub_address")?,
+                    field("author")?,
+                    field("title")?,
+                    field("flair")?,
+                    field("content")?,
+                    slot,
+                ],
+            )?;
+        }
+        "PostEdited" => {
+            tx.execute(
+                "UPDATE posts SET content = ?2, flair = ?3, updated_slot = ?4 WHERE address = ?1",
+                params![field("post_address")?, field("content")?, field("flair")?, slot],
+            )?;
+        }
+        "PostDeleted" => {
+            tx.execute(
+                "UPDATE posts SET content = '', status = 'DELETED', updated_slot = ?2 WHERE address = ?1",
+                params![field("post_address")?, slot],
+            )?;
+        }
+        "PostArchived" => {
+            tx.execute(
+                "UPDATE posts SET status = 'ARCHIVED', updated_slot = ?2 WHERE address = ?1",
+                params![field("post_address")?, slot],
+            )?;
+        }
+        "PostVoted" => apply_vote(tx, &field("post_address")?, &field("voter")?, &field("direction")?, body, slot)?,
+        "CommentCreated" => {
+            let parent = body.get("parent_comment").and_then(Value::as_str);
+            tx.execute(
+                "INSERT OR IGNORE INTO comments (address, post_address, parent_address, author, content, created_slot, updated_slot)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                params![field("comment_address")?, field("post_address")?, parent, field("author")?, field("content")?, slot],
+            )?;
+        }
+        "CommentEdited" => {
+            tx.execute(
+                "UPDATE comments SET content = ?2, updated_slot = ?3 WHERE address = ?1",
+                params![field("comment_address")?, field("content")?, slot],
+            )?;
+        }
+        "CommentDeleted" => {
+            tx.execute(
+                "UPDATE comments SET content = '', deleted = 1, updated_slot = ?2 WHERE address = ?1",
+                params![field("comment_address")?, slot],
+            )?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Moderator changes are part of the subreddit record in the rollup state
+fn touch_subreddit(tx: &Transaction, sub_address: &str, slot: i64) -> anyhow::Result<()> {
+    tx.execute(
+        "UPDATE subreddits SET updated_slot = ?2 WHERE address = ?1",
+        params![sub_address, slot],
+    )?;
+    Ok(())
+}
+
+fn all_permissions() -> String {
+    serde_json::to_string(&ModPermission::all()).expect("ModPermission serialization cannot fail")
+}
+
+/// Replaces the voter's previous vote and moves the score change over to the author's karma
+fn apply_vote(tx: &Transaction, post: &str, voter: &str, direction: &str, body: &Value, slot: i64) -> anyhow::Result<()> {
+    if direction == "NONE" {
+        tx.execute("DELETE FROM votes WHERE post_address = ?1 AND voter = ?2", params![post, voter])?;
+    } else {
+        tx.execute(
+            "INSERT INTO votes (post_address, voter, direction, slot) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (post_address, voter) DO UPDATE SET direction = excluded.direction, slot = excluded.slot",
+            params![post, voter, direction, slot],
+        )?;
+    }
+
+    let score = body.get("score").and_then(Value::as_i64).unwrap_or_default();
+    let previous: Option<(i64, String)> = tx
+        .query_row("SELECT score, author FROM posts WHERE address = ?1", params![post], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+
+    if let Some((previous_score, author)) = previous {
+        tx.execute(
+            "UPDATE users SET karma = karma + ?2, updated_slot = ?3 WHERE address = ?1",
+            params![author, score - previous_score, slot],
+        )?;
+    }
+
+    tx.execute(
+        "UPDATE posts SET score = ?2, updated_slot = ?3,
+            upvotes = (SELECT COUNT(*) FROM votes WHERE post_address = ?1 AND direction = 'UP'),
+            downvotes = (SELECT COUNT(*) FROM votes WHERE post_address = ?1 AND direction = 'DOWN')
+         WHERE address = ?1",
+        params![post, score, slot],
+    )?;
+    Ok(())
+}
+
+
+/// Runs the `sqlite` offchain sink on a [`crate::offchain::SinkWriter`] thread. A change that fails to apply
+/// is logged and retried, later changes wait behind it so the tables never skip one
+impl ChangeWriter for Indexer {
+    fn write(&mut self, envelope: &RedditChangeEnvelope) -> anyhow::Result<()> {
+        self.apply(envelope)
+            .map(|_| ())
+            .map_err(|e| e.context(format!("Failed to apply change {}", envelope.sequence)))
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Indexer::flush(self).map(|_| ()).map_err(|e| e.context("Failed to roll back reverted changes"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offchain::{RedditCollections, RedditStateChanges, SCHEMA_VERSION};
+    use serde_json::json;
+
+    fn envelope(sequence: u64, slot_height: u64, state: RedditCollections, address: &str, event: Value) -> RedditChangeEnvelope {
+        RedditChangeEnvelope {
+            schema_version: SCHEMA_VERSION,
+            sequence,
+            slot_height,
+            slot_hash: Some(format!("0x{:02}", slot_height)),
+            tx_hash: None,
+            changes: RedditStateChanges {
+                state,
+                change: event.to_string(),
+                address: address.to_string(),
+                change_type: ChangeType::CREATED,
+            },
+        }
+    }
+
+    /// Two users, a subreddit and a post
+    fn slot_1() -> Vec<RedditChangeEnvelope> {
+        vec![
+            envelope(0, 1, RedditCollections::USER, "alice", json!({"UserCreated": {"user_address": "alice", "username": "alice", "owner": "a"}})),
+            envelope(1, 1, RedditCollections::USER, "bob", json!({"UserCreated": {"user_address": "bob", "username": "bob", "owner": "b"}})),
+            envelope(2, 1, RedditCollections::SUBREDDIT, "rust", json!({"SubredditCreated": {"sub_address": "rust", "subname": "rust", "description": "", "creator": "alice"}})),
+            envelope(3, 1, RedditCollections::POST, "post", json!({"PostCreated": {"post_address": "post", "sub_address": "rust", "author": "alice", "title": "t", "flair": "f", "content": "c"}})),
+        ]
+    }
+
+    /// A vote on the post, a comment and an edit
+    fn slot_2() -> Vec<RedditChangeEnvelope> {
+        vec![
+            envelope(4, 2, RedditCollections::POST, "post", json!({"PostVoted": {"post_address": "post", "voter": "bob", "direction": "UP", "score": 1}})),
+            envelope(5, 2, RedditCollections::COMMENT, "comment", json!({"CommentCreated": {"comment_address": "comment", "post_address": "post", "parent_comment": null, "author": "bob", "content": "hi"}})),
+            envelope(6, 2, RedditCollections::POST, "post", json!({"PostEdited": {"post_address": "post", "content": "edited", "flair": "f"}})),
+        ]
+    }
+
+    /// A second vote
+    fn slot_3() -> Vec<RedditChangeEnvelope> {
+        vec![envelope(7, 3, RedditCollections::POST, "post", json!({"PostVoted": {"post_address": "post", "voter": "alice", "direction": "DOWN", "score": 0}}))]
+    }
+
+    /// Bob moderates, alice subscribes, carol gets banned and appeals
+    fn moderation_slot() -> Vec<RedditChangeEnvelope> {
+        let sub = RedditCollections::SUBREDDIT;
+        vec![
+            envelope(8, 4, RedditCollections::USER, "carol", json!({"UserCreated": {"user_address": "carol", "username": "carol", "owner": "c"}})),
+            envelope(9, 4, sub, "rust", json!({"ModeratorAdded": {"sub_address": "rust", "moderator": "bob", "permissions": ["MANAGE_POSTS"], "added_by": "alice"}})),
+            envelope(10, 4, sub, "rust", json!({"ModeratorPermissionsUpdated": {"sub_address": "rust", "moderator": "bob", "permissions": ["MANAGE_USERS"], "updated_by": "alice"}})),
+            envelope(11, 4, sub, "rust", json!({"Subscribed": {"sub_address": "rust", "user_address": "alice"}})),
+            envelope(12, 4, sub, "rust", json!({"Subscribed": {"sub_address": "rust", "user_address": "carol"}})),
+            envelope(13, 4, sub, "rust", json!({"UserBanned": {"sub_address": "rust", "user_address": "carol", "banned_by": "bob", "until_slot": 50, "reason": "spam"}})),
+            envelope(14, 4, sub, "rust", json!({"UserMuted": {"sub_address": "rust", "user_address": "carol", "muted_by": "bob", "until_slot": null}})),
+            envelope(15, 4, sub, "rust", json!({"BanAppealed": {"sub_address": "rust", "user_address": "carol"}})),
+            envelope(16, 4, sub, "rust", json!({"Unsubscribed": {"sub_address": "rust", "user_address": "carol"}})),
+        ]
+    }
+
+    /// Carol's appeal is dismissed, she appeals again and is unbanned, bob takes over the subreddit
+    fn resolution_slot() -> Vec<RedditChangeEnvelope> {
+        let sub = RedditCollections::SUBREDDIT;
+        vec![
+            envelope(17, 5, sub, "rust", json!({"AppealDismissed": {"sub_address": "rust", "user_address": "carol", "dismissed_by": "bob"}})),
+            envelope(18, 5, sub, "rust", json!({"BanAppealed": {"sub_address": "rust", "user_address": "carol"}})),
+            envelope(19, 5, sub, "rust", json!({"UserUnbanned": {"sub_address": "rust", "user_address": "carol", "unbanned_by": "bob"}})),
+            envelope(20, 5, sub, "rust", json!({"OwnershipTransferred": {"sub_address": "rust", "previous_owner": "alice", "new_owner": "bob"}})),
+            envelope(21, 5, sub, "rust", json!({"ModeratorRemoved": {"sub_address": "rust", "moderator": "alice", "removed_by": "alice"}})),
+        ]
+    }
+
+    /// Reverts of `envelopes` as the journal publishes them, newest first
+    fn reverted(envelopes: &[RedditChangeEnvelope]) -> Vec<RedditChangeEnvelope> {
+        envelopes
+            .iter()
+            .rev()
+            .cloned()
+            .map(|mut envelope| {
+                envelope.changes.change_type = ChangeType::REVERTED;
+                envelope
+            })
+            .collect()
+    }
+
+    fn undo_log_len(indexer: &Indexer) -> i64 {
+        indexer.connection().query_row("SELECT COUNT(*) FROM undo_log", [], |row| row.get(0)).unwrap()
+    }
+
+    fn apply_all(indexer: &mut Indexer, envelopes: &[RedditChangeEnvelope]) {
+        for envelope in envelopes {
+            indexer.apply(envelope).unwrap();
+        }
+        indexer.flush().unwrap();
+    }
+
+    /// Every row of every table but `applied_changes`
+    fn dump(indexer: &Indexer) -> Vec<String> {
+        let queries = [
+            "SELECT address || ' ' || username || ' ' || karma || ' ' || updated_slot FROM users ORDER BY address",
+            "SELECT address || ' ' || owner || ' ' || updated_slot FROM subreddits ORDER BY address",
+            "SELECT address || ' ' || content || ' ' || status || ' ' || score || ' ' || upvotes || ' ' || downvotes || ' ' || updated_slot FROM posts ORDER BY address",
+            "SELECT address || ' ' || content || ' ' || deleted || ' ' || updated_slot FROM comments ORDER BY address",
+            "SELECT post_address || ' ' || voter || ' ' || direction || ' ' || slot FROM votes ORDER BY post_address, voter",
+            "SELECT 'mod ' || sub_address || ' ' || moderator || ' ' || permissions || ' ' || updated_slot FROM moderators ORDER BY sub_address, moderator",
+            "SELECT 'ban ' || sub_address || ' ' || user_address || ' ' || reason || ' ' || ifnull(until_slot, '-') FROM bans ORDER BY sub_address, user_address",
+            "SELECT 'mute ' || sub_address || ' ' || user_address || ' ' || ifnull(until_slot, '-') FROM mutes ORDER BY sub_address, user_address",
+            "SELECT 'appeal ' || sub_address || ' ' || user_address FROM appeals ORDER BY sub_address, user_address",
+            "SELECT 'sub ' || sub_address || ' ' || user_address FROM subscriptions ORDER BY sub_address, user_address",
+        ];
+
+        let mut rows = vec![];
+        for query in queries {
+            let mut statement = indexer.connection().prepare(query).unwrap();
+            rows.extend(statement.query_map([], |row| row.get::<_, String>(0)).unwrap().map(Result::unwrap));
+            rows.push(String::new());
+        }
+        rows
+    }
+
+    #[test]
+    fn revert_and_reapply_restores_the_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+
+        apply_all(&mut indexer, &slot_1());
+        let after_slot_1 = dump(&indexer);
+        apply_all(&mut indexer, &slot_2());
+        let after_slot_2 = dump(&indexer);
+        assert_ne!(after_slot_1, after_slot_2);
+        assert!(after_slot_2.contains(&"alice alice 1 2".to_string()));
+        assert!(after_slot_2.contains(&"post bob UP 2".to_string()));
+
+        apply_all(&mut indexer, &reverted(&slot_2()));
+        assert_eq!(dump(&indexer), after_slot_1);
+        assert_eq!(indexer.last_sequence().unwrap(), Some(3));
+
+        apply_all(&mut indexer, &slot_2());
+        assert_eq!(dump(&indexer), after_slot_2);
+        assert_eq!(indexer.last_sequence().unwrap(), Some(6));
+    }
+
+    #[test]
+    fn rolls_back_a_run_of_reverts_once_to_the_oldest_slot() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+
+        apply_all(&mut indexer, &slot_1());
+        let after_slot_1 = dump(&indexer);
+        apply_all(&mut indexer, &slot_2());
+        apply_all(&mut indexer, &slot_3());
+
+        let mut reverts = reverted(&slot_3());
+        reverts.extend(reverted(&slot_2()));
+        for envelope in &reverts {
+            indexer.apply(envelope).unwrap();
+        }
+        assert_eq!(indexer.flush().unwrap(), 4);
+        assert_eq!(indexer.flush().unwrap(), 0);
+        assert_eq!(dump(&indexer), after_slot_1);
+    }
+
+    #[test]
+    fn the_next_change_runs_the_pending_rollback_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+
+        apply_all(&mut indexer, &slot_1());
+        apply_all(&mut indexer, &slot_2());
+
+        // A different slot 2 replaces the reverted one without an explicit flush
+        for envelope in reverted(&slot_2()).iter().chain(&slot_3()) {
+            indexer.apply(envelope).unwrap();
+        }
+        let votes = dump(&indexer).into_iter().filter(|row| row.starts_with("post ") && row.ends_with(" 3")).count();
+        assert_eq!(votes, 2);
+        assert!(!dump(&indexer).iter().any(|row| row.starts_with("comment ")));
+    }
+
+    #[test]
+    fn skips_changes_it_already_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+
+        apply_all(&mut indexer, &slot_1());
+        let after_slot_1 = dump(&indexer);
+
+        assert!(!indexer.apply(&slot_1()[0]).unwrap());
+        assert_eq!(dump(&indexer), after_slot_1);
+    }
+
+    #[test]
+    fn tracks_moderators_bans_and_subscriptions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+        apply_all(&mut indexer, &slot_1());
+        assert!(dump(&indexer).iter().any(|row| row.starts_with("mod rust alice [\"MANAGE_POSTS\"")));
+
+        apply_all(&mut indexer, &moderation_slot());
+        let rows = dump(&indexer);
+        for row in [
+            "mod rust bob [\"MANAGE_USERS\"] 4",
+            "ban rust carol spam 50",
+            "mute rust carol -",
+            "appeal rust carol",
+            "sub rust alice",
+            "rust alice 4",
+        ] {
+            assert!(rows.contains(&row.to_string()), "missing {}", row);
+        }
+        assert!(!rows.contains(&"sub rust carol".to_string()));
+
+        apply_all(&mut indexer, &resolution_slot());
+        let rows = dump(&indexer);
+        assert!(!rows.iter().any(|row| row.starts_with("ban ") || row.starts_with("appeal ") || row.starts_with("mod rust alice")));
+        assert!(rows.iter().any(|row| row.starts_with("mod rust bob [\"MANAGE_POSTS\",\"MANAGE_USERS\"")));
+        assert!(rows.contains(&"rust bob 5".to_string()));
+    }
+
+    #[test]
+    fn reverts_undo_deleted_and_replaced_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+
+        apply_all(&mut indexer, &slot_1());
+        apply_all(&mut indexer, &slot_2());
+        apply_all(&mut indexer, &slot_3());
+        apply_all(&mut indexer, &moderation_slot());
+        let after_moderation = dump(&indexer);
+        let undo_entries = undo_log_len(&indexer);
+
+        apply_all(&mut indexer, &resolution_slot());
+        assert!(undo_log_len(&indexer) > undo_entries);
+        assert_eq!(indexer.rollback_to_slot(5).unwrap(), 5);
+        assert_eq!(dump(&indexer), after_moderation);
+        // Only the undo statements of the reverted slot were used up
+        assert_eq!(undo_log_len(&indexer), undo_entries);
+
+        // A reopened indexer undoes the changes of an earlier run
+        drop(indexer);
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+        apply_all(&mut indexer, &reverted(&moderation_slot()));
+        apply_all(&mut indexer, &reverted(&slot_3()));
+        let mut replayed = Indexer::open(&dir.path().join("replayed.sqlite")).unwrap();
+        apply_all(&mut replayed, &slot_1());
+        apply_all(&mut replayed, &slot_2());
+        assert_eq!(dump(&indexer), dump(&replayed));
+    }
+
+    #[test]
+    fn rebuilds_the_tables_for_a_rollback_older_than_the_undo_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut indexer = Indexer::open(&dir.path().join("reddit.sqlite")).unwrap();
+        apply_all(&mut indexer, &slot_1());
+        let after_slot_1 = dump(&indexer);
+
+        // Slot 2 lands so far after slot 1 that the undo statements of slot 1 are dropped
+        let late = 2 + MAX_JOURNAL_SLOTS as u64;
+        let mut slot_2 = slot_2();
+        for envelope in &mut slot_2 {
+            envelope.slot_height = late;
+        }
+        apply_all(&mut indexer, &slot_2);
+        let floor: i64 = indexer.connection().query_row("SELECT floor FROM undo_state", [], |row| row.get(0)).unwrap();
+        assert_eq!(floor, 4);
+
+        assert_eq!(indexer.rollback_to_slot(late).unwrap(), 3);
+        assert_eq!(dump(&indexer), after_slot_1);
+
+        assert_eq!(indexer.rollback_to_slot(1).unwrap(), 4);
+        assert!(dump(&indexer).iter().all(String::is_empty));
+        assert_eq!(undo_log_len(&indexer), 0);
+        assert_eq!(indexer.last_sequence().unwrap(), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Remembers which changes were published for the most recent DA slots so the offchain
+//! pipeline can tell a replayed slot from a reorged one.
+//!
+//! The journal is an append-only log with one line per committed slot or revert, synced before the
+//! changes are handed to the sink. That append is the only blocking write the offchain pipeline does
+//! while a slot executes. The log is rewritten from the retained slots when it grows too long
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::{self, File, OpenOptions}, io::{self, BufRead, BufReader, Write}, path::{Path, PathBuf}};
+
+use crate::offchain::{ChangeType, RedditChangeEnvelope};
+
+
+/// File created inside the node's storage path
+pub const JOURNAL_FILE: &str = "reddit-offchain-journal.jsonl";
+
+/// Slots kept in the journal, a reorg deeper than this is not compensated
+pub const MAX_JOURNAL_SLOTS: usize = 256;
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct JournalSlot {
+    hash: String,
+    envelopes: Vec<RedditChangeEnvelope>,
+}
+
+
+/// One line of the journal file
+#[derive(Serialize, Deserialize, Debug)]
+enum JournalRecord {
+    Slot { height: u64, slot: JournalSlot },
+    /// Every slot from `height` on was reverted by a reorg
+    Revert { height: u64 },
+}
+
+
+/// What the pipeline has to do with the changes of a slot that just began
+#[derive(Debug)]
+pub enum SlotStart {
+    /// First execution of this slot
+    New,
+    /// Same slot executed again, its changes were already published
+    Replay,
+    /// The chain switched to a different block at this height, the returned envelopes cancel
+    /// everything published for this and later slots, newest first
+    Reorg(Vec<RedditChangeEnvelope>),
+}
+
+
+#[derive(Default)]
+pub struct SlotJournal {
+    path: Option<PathBuf>,
+    /// Committed slots only, the one being executed is added by [`SlotJournal::commit_slot`]
+    slots: BTreeMap<u64, JournalSlot>,
+    /// Slot being executed, may be lower than the highest committed one while the node replays
+    current: Option<(u64, String)>,
+    replaying: bool,
+    /// Records appended since the file was last rewritten
+    appended: usize,
+}
+
+impl SlotJournal {
+    /// Loads the journal kept under `storage_path` by a previous run
+    pub fn open(storage_path: &Path) -> anyhow::Result<Self> {
+        let path = storage_path.join(JOURNAL_FILE);
+        let mut journal = SlotJournal {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    // A crash during an append leaves a partial last line, that slot was never published
+                    let Ok(record) = serde_json::from_str(&line?) else {
+                        break;
+                    };
+                    match record {
+                        JournalRecord::Slot { height, slot } => journal.insert(height, slot),
+                        JournalRecord::Revert { height } => {
+                            journal.slots.split_off(&height);
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        // Drops reverted slots and any partial line before new records are appended
+        journal.compact()?;
+        Ok(journal)
+    }
+
+    /// Hash of the slot currently being executed
+    pub fn current_hash(&self) -> Option<&str> {
+        self.current.as_ref().map(|(_, hash)| hash.as_str())
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Highest slot whose changes were published
+    pub fn last_committed_height(&self) -> Option<u64> {
+        self.slots.last_key_value().map(|(height, _)| *height)
+    }
+
+    /// Compares the slot with the committed ones. A reorg is written to disk before it is returned,
+    /// so its reverts are never published twice
+    pub fn begin_slot(&mut self, height: u64, hash: String) -> anyhow::Result<SlotStart> {
+        let start = match self.slots.get(&height) {
+            Some(slot) if slot.hash == hash => SlotStart::Replay,
+            Some(_) => SlotStart::Reorg(self.revert_from(height)?),
+            None if self.slots.range(height..).next().is_some() => SlotStart::Reorg(self.revert_from(height)?),
+            None => SlotStart::New,
+        };
+
+        self.replaying = matches!(start, SlotStart::Replay);
+        self.current = Some((height, hash));
+        Ok(start)
+    }
+
+    /// Records the changes of the current slot once it is committed, nothing is recorded for a replayed slot
+    pub fn commit_slot(&mut self, envelopes: &[RedditChangeEnvelope]) -> anyhow::Result<()> {
+        if self.replaying {
+            return Ok(());
+        }
+        let Some((height, hash)) = self.current.clone() else {
+            return Ok(());
+        };
+
+        let slot = JournalSlot { hash, envelopes: envelopes.to_vec() };
+        self.append(&JournalRecord::Slot { height, slot: slot.clone() })?;
+        self.insert(height, slot);
+
+        if self.appended > 2 * MAX_JOURNAL_SLOTS {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, height: u64, slot: JournalSlot) {
+        self.slots.insert(height, slot);
+        while self.slots.len() > MAX_JOURNAL_SLOTS {
+            self.slots.pop_first();
+        }
+    }
+
+    /// Drops every slot from `height` on and returns their changes as REVERTED envelopes, newest first
+    fn revert_from(&mut self, height: u64) -> anyhow::Result<Vec<RedditChangeEnvelope>> {
+        self.append(&JournalRecord::Revert { height })?;
+        let reverted = self.slots.split_off(&height);
+
+        Ok(reverted
+            .into_values()
+            .rev()
+            .flat_map(|slot| slot.envelopes.into_iter().rev())
+            .map(|mut envelope| {
+                envelope.changes.change_type = ChangeType::REVERTED;
+                envelope
+            })
+            .collect())
+    }
+
+    fn append(&mut self, record: &JournalRecord) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        self.appended += 1;
+        Ok(())
+    }
+
+    /// Rewrites the file with one record per retained slot
+    fn compact(&mut self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut bytes = Vec::new();
+        for (height, slot) in &self.slots {
+            serde_json::to_writer(&mut bytes, &JournalRecord::Slot { height: *height, slot: slot.clone() })?;
+            bytes.push(b'\n');
+        }
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        File::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, path)?;
+        if let Some(dir) = path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        self.appended = 0;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::envelope;
+
+    /// Begins and commits slots `1..=count`, slot `h` has the hash `a{h}` and the single change `h`
+    fn commit_slots(journal: &mut SlotJournal, count: u64) {
+        for height in 1..=count {
+            assert!(matches!(journal.begin_slot(height, format!("a{}", height)).unwrap(), SlotStart::New));
+            journal.commit_slot(&[envelope(height, height)]).unwrap();
+        }
+    }
+
+    #[test]
+    fn replays_committed_slots_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        commit_slots(&mut SlotJournal::open(dir.path()).unwrap(), 3);
+
+        let mut journal = SlotJournal::open(dir.path()).unwrap();
+        assert_eq!(journal.last_committed_height(), Some(3));
+        for height in 1..=3 {
+            assert!(matches!(journal.begin_slot(height, format!("a{}", height)).unwrap(), SlotStart::Replay));
+            journal.commit_slot(&[envelope(height, height)]).unwrap();
+        }
+        assert!(matches!(journal.begin_slot(4, "a4".to_string()).unwrap(), SlotStart::New));
+    }
+
+    #[test]
+    fn replaying_an_older_slot_keeps_the_newer_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = SlotJournal::open(dir.path()).unwrap();
+        commit_slots(&mut journal, 3);
+
+        assert!(matches!(journal.begin_slot(2, "a2".to_string()).unwrap(), SlotStart::Replay));
+        journal.commit_slot(&[]).unwrap();
+        assert_eq!(journal.last_committed_height(), Some(3));
+
+        let mut journal = SlotJournal::open(dir.path()).unwrap();
+        assert_eq!(journal.last_committed_height(), Some(3));
+        assert!(matches!(journal.begin_slot(3, "a3".to_string()).unwrap(), SlotStart::Replay));
+    }
+
+    #[test]
+    fn reorg_reverts_the_slot_and_everything_after_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = SlotJournal::open(dir.path()).unwrap();
+        commit_slots(&mut journal, 3);
+
+        let SlotStart::Reorg(reverted) = journal.begin_slot(2, "b2".to_string()).unwrap() else {
+            panic!("A different hash at a committed height is a reorg");
+        };
+        let sequences: Vec<_> = reverted.iter().map(|envelope| envelope.sequence).collect();
+        assert_eq!(sequences, vec![3, 2]);
+        assert!(reverted.iter().all(|envelope| envelope.changes.change_type == ChangeType::REVERTED));
+        journal.commit_slot(&[envelope(2, 2)]).unwrap();
+
+        // The revert was persisted, the old branch is not reverted a second time
+        let mut journal = SlotJournal::open(dir.path()).unwrap();
+        assert_eq!(journal.last_committed_height(), Some(2));
+        assert!(matches!(journal.begin_slot(2, "b2".to_string()).unwrap(), SlotStart::Replay));
+        assert!(matches!(journal.begin_slot(3, "b3".to_string()).unwrap(), SlotStart::New));
+    }
+
+    #[test]
+    fn ignores_a_partially_written_record() {
+        let dir = tempfile::tempdir().unwrap();
+        commit_slots(&mut SlotJournal::open(dir.path()).unwrap(), 2);
+
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(JOURNAL_FILE)).unwrap();
+        file.write_all(b"{\"Slot\":{\"height\":3,").unwrap();
+
+        let mut journal = SlotJournal::open(dir.path()).unwrap();
+        assert_eq!(journal.last_committed_height(), Some(2));
+        assert!(matches!(journal.begin_slot(3, "a3".to_string()).unwrap(), SlotStart::New));
+        journal.commit_slot(&[envelope(3, 3)]).unwrap();
+        assert_eq!(SlotJournal::open(dir.path()).unwrap().last_committed_height(), Some(3));
+    }
+
+    #[test]
+    fn keeps_only_the_latest_slots() {
+        let dir = tempfile::tempdir().unwrap();
+        let count = 2 * MAX_JOURNAL_SLOTS as u64 + 10;
+        commit_slots(&mut SlotJournal::open(dir.path()).unwrap(), count);
+
+        let journal = SlotJournal::open(dir.path()).unwrap();
+        assert_eq!(journal.slots.len(), MAX_JOURNAL_SLOTS);
+        assert_eq!(journal.last_committed_height(), Some(count));
+    }
+}
@@ -1,22 +1,49 @@
 use std::ops::Sub;
 
-use address::{PostAddress, SubAddress, UserAddress};
+use address::{CommentAddress, PostAddress, SubAddress, UserAddress};
+use ban::{Appeal, Ban};
 use call::CallMessage;
+use comment::Comment;
+use event::RedditEvent;
 use post::Post;
+use ranking::{FeedKey, FeedNode, FeedSort, RankEntry};
+use ratelimit::{RateLimitedAction, RateLimits};
 use serde::{Deserialize, Serialize};
-use sov_modules_api::{CallResponse, Context, Error, Module, ModuleInfo, StateMap, WorkingSet};
+use sov_modules_api::{AccessoryStateMap, AccessoryStateValue, CallResponse, Context, Error, Module, ModuleInfo, StateMap, StateValue, StateValueAccessor, WorkingSet};
 use subreddit::SubReddit;
 use user::User;
+use vote::VoteDirection;
 
 pub mod call;
+#[cfg(feature = "native")]
 pub mod query;
 pub mod user;
 pub mod address;
 pub mod utils;
 pub mod subreddit;
 pub mod post;
+pub mod comment;
+pub mod vote;
+pub mod event;
+pub mod error;
+pub mod moderator;
+pub mod ban;
+#[cfg(feature = "native")]
 pub mod offchain;
+#[cfg(feature = "native")]
+pub mod outbox;
+#[cfg(feature = "native")]
+pub mod indexer;
+#[cfg(feature = "native")]
+pub mod journal;
 pub mod hooks;
+pub mod ranking;
+pub mod timestamp;
+pub mod ratelimit;
+#[cfg(feature = "native")]
+pub mod signature;
+#[cfg(test)]
+mod test_utils;
 
 
 #[cfg_attr(feature = "native", derive(sov_modules_api::ModuleCallJsonSchema))]
@@ -26,8 +53,17 @@ pub struct Reddit<C: Context> {
     pub address: C::Address,
 
 
-    // #[state]
-    // pub user_address_collections:  StateMap<C::Address , UserAddress<C>>,
+    /// The single user profile owned by each sender
+    #[state]
+    pub user_address_collections:  StateMap<C::Address , UserAddress<C>>,
+
+    /// Global username registry keyed by the canonical username
+    #[state]
+    pub username_collections: StateMap<String, UserAddress<C>>,
+
+    /// Canonical names that can be used neither as username nor as subreddit name
+    #[state]
+    pub reserved_names: StateValue<Vec<String>>,
 
     #[state]
     pub user_collections: StateMap<UserAddress<C>, User<C>>,
@@ -36,11 +72,112 @@ pub struct Reddit<C: Context> {
     pub sub_collections: StateMap<SubAddress<C>, SubReddit<C>>,
 
     #[state]
-    pub post_collections: StateMap<PostAddress<C> , Post<C>>
+    pub post_collections: StateMap<PostAddress<C> , Post<C>>,
+
+    /// Number of posts created by each author, used to derive post addresses
+    #[state]
+    pub post_nonce_collections: StateMap<UserAddress<C>, u64>,
+
+    /// Posts of each author by position, the position is the post nonce
+    #[state]
+    pub user_post_index: StateMap<(UserAddress<C>, u64), PostAddress<C>>,
+
+    /// Posts of each subreddit by position, in creation order
+    #[state]
+    pub sub_post_index: StateMap<(SubAddress<C>, u64), PostAddress<C>>,
+
+    #[state]
+    pub sub_post_counts: StateMap<SubAddress<C>, u64>,
+
+    #[state]
+    pub comment_collections: StateMap<CommentAddress<C>, Comment<C>>,
+
+    /// Top level comments of each post, replies are stored on the parent comment
+    #[state]
+    pub post_comment_collections: StateMap<PostAddress<C>, Vec<CommentAddress<C>>>,
+
+    #[state]
+    pub post_vote_collections: StateMap<(PostAddress<C>, UserAddress<C>), VoteDirection>,
+
+    /// Subreddits each user is subscribed to, in subscription order
+    #[state]
+    pub subscription_collections: StateMap<UserAddress<C>, Vec<SubAddress<C>>>,
+
+    #[state]
+    pub subscriber_counts: StateMap<SubAddress<C>, u64>,
+
+    #[state]
+    pub ban_collections: StateMap<(SubAddress<C>, UserAddress<C>), Ban<C>>,
+
+    /// Users with a ban record in each subreddit, used to list bans
+    #[state]
+    pub sub_ban_list: StateMap<SubAddress<C>, Vec<UserAddress<C>>>,
+
+    /// Slot until which a user is muted in a subreddit, `None` is permanent
+    #[state]
+    pub mute_collections: StateMap<(SubAddress<C>, UserAddress<C>), Option<u64>>,
+
+    /// Pending ban appeals of each subreddit, oldest first
+    #[state]
+    pub appeal_queue: StateMap<SubAddress<C>, Vec<Appeal<C>>>,
+
+    /// Number of state changes emitted so far, numbers the offchain change envelopes.
+    /// Only native nodes keep it, it is not part of the state root
+    #[state]
+    pub change_sequence: AccessoryStateValue<u64>,
+
+    /// Height of the DA slot currently being executed, set by the begin slot hook
+    #[state]
+    pub current_slot_height: StateValue<u64>,
+
+    /// Unix timestamp of the DA block currently being executed
+    #[state]
+    pub current_slot_timestamp: StateValue<i64>,
+
+    /// Global number of slots after which posts are archived, unset means never
+    #[state]
+    pub archive_after_slots: StateValue<u64>,
+
+    /// Posts to archive at the end of each slot, keyed by slot height
+    #[state]
+    pub archive_queue: StateMap<u64, Vec<PostAddress<C>>>,
+
+    #[state]
+    pub rate_limits: StateValue<RateLimits>,
+
+    /// Slots of each user's recent rate limited actions, trimmed to the quota window
+    #[state]
+    pub rate_limit_log: StateMap<(UserAddress<C>, RateLimitedAction), Vec<u64>>,
+
+    /// Posts whose votes or status changed in the current slot, drained by the finalize hook
+    #[state]
+    pub pending_rank_updates: AccessoryStateValue<Vec<RankEntry<C>>>,
+
+    /// Posts of each subreddit feed, linked in feed order as a skip list
+    #[state]
+    pub feed_entries: AccessoryStateMap<(SubAddress<C>, FeedSort, FeedKey<C>), FeedNode<C>>,
+
+    /// First entry of each feed on every skip list level
+    #[state]
+    pub feed_heads: AccessoryStateMap<(SubAddress<C>, FeedSort), Vec<Option<FeedKey<C>>>>,
+
+    /// Current ranking key of each post in each of its feeds
+    #[state]
+    pub feed_ranks: AccessoryStateMap<(PostAddress<C>, FeedSort), i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
-pub struct RedditConfig {}
+pub struct RedditConfig {
+    /// Names nobody can register, e.g. "admin" or "mod"
+    #[serde(default)]
+    pub reserved_names: Vec<String>,
+    /// Posts are archived this many slots after creation, `None` keeps them active forever
+    #[serde(default)]
+    pub archive_after_slots: Option<u64>,
+    /// Quotas enforced before reddit calls are dispatched, all unlimited by default
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+}
 
 
 impl<C:Context> Module for Reddit<C> {
@@ -51,12 +188,23 @@ impl<C:Context> Module for Reddit<C> {
     type CallMessage = CallMessage<C>;
 
 
-        type Event = ();
+        type Event = RedditEvent<C>;
       fn genesis(
         &self,
-        _config: &Self::Config,
-        _working_set: &mut WorkingSet<C>,
+        config: &Self::Config,
+        working_set: &mut WorkingSet<C>,
     ) -> Result<(), Error> {
+        let reserved_names: Vec<String> = config
+            .reserved_names
+            .iter()
+            .map(|name| name.to_ascii_lowercase())
+            .collect();
+        self.reserved_names.set(&reserved_names, working_set);
+
+        if let Some(archive_after_slots) = config.archive_after_slots {
+            self.archive_after_slots.set(&archive_after_slots, working_set);
+        }
+        self.rate_limits.set(&config.rate_limits, working_set);
         Ok(())
     }
 
@@ -72,7 +220,7 @@ impl<C:Context> Module for Reddit<C> {
                 username,
             } => self.create_new_user(&username, context, working_set),
             CallMessage::CreateSubReddit { user_address , subname , description } => {
-                self.create_new_subreddit(&subname, &description, context , working_set)
+                self.create_new_subreddit(UserAddress::new(&user_address), &subname, &description, context , working_set)
             }
             CallMessage::CreatePost {
                title,
@@ -87,6 +235,131 @@ impl<C:Context> Module for Reddit<C> {
                 context,
                 working_set,
             ),
+            CallMessage::EditPost {
+                post_address,
+                content,
+                flair,
+            } => self.edit_post(
+                PostAddress::new(&post_address),
+                content.as_deref(),
+                flair.as_deref(),
+                context,
+                working_set,
+            ),
+            CallMessage::DeletePost { post_address } => {
+                self.delete_post(PostAddress::new(&post_address), context, working_set)
+            }
+            CallMessage::ArchiveSubreddit { sub_address } => {
+                self.archive_subreddit(SubAddress::new(&sub_address), context, working_set)
+            }
+            CallMessage::SetPostFlair { post_address, flair } => {
+                self.set_post_flair(PostAddress::new(&post_address), &flair, context, working_set)
+            }
+            CallMessage::SetArchiveAfterSlots {
+                sub_address,
+                archive_after_slots,
+            } => self.set_archive_after_slots(
+                SubAddress::new(&sub_address),
+                archive_after_slots,
+                context,
+                working_set,
+            ),
+            CallMessage::AddModerator { sub_address, user_address, permissions } => self.add_moderator(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                permissions,
+                context,
+                working_set,
+            ),
+            CallMessage::SetModeratorPermissions { sub_address, user_address, permissions } => self
+                .set_moderator_permissions(
+                    SubAddress::new(&sub_address),
+                    UserAddress::new(&user_address),
+                    permissions,
+                    context,
+                    working_set,
+                ),
+            CallMessage::RemoveModerator { sub_address, user_address } => self.remove_moderator(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                context,
+                working_set,
+            ),
+            CallMessage::LeaveModerator { sub_address } => {
+                self.leave_moderator(SubAddress::new(&sub_address), context, working_set)
+            }
+            CallMessage::TransferOwnership { sub_address, user_address } => self.transfer_ownership(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                context,
+                working_set,
+            ),
+            CallMessage::BanUser {
+                sub_address,
+                user_address,
+                until_slot,
+                reason,
+            } => self.ban_user(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                until_slot,
+                &reason,
+                context,
+                working_set,
+            ),
+            CallMessage::UnbanUser { sub_address, user_address } => self.unban_user(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                context,
+                working_set,
+            ),
+            CallMessage::MuteUser {
+                sub_address,
+                user_address,
+                until_slot,
+            } => self.mute_user(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                until_slot,
+                context,
+                working_set,
+            ),
+            CallMessage::AppealBan { sub_address, message } => {
+                self.appeal_ban(SubAddress::new(&sub_address), &message, context, working_set)
+            }
+            CallMessage::DismissAppeal { sub_address, user_address } => self.dismiss_appeal(
+                SubAddress::new(&sub_address),
+                UserAddress::new(&user_address),
+                context,
+                working_set,
+            ),
+            CallMessage::Subscribe { sub_address } => {
+                self.subscribe(SubAddress::new(&sub_address), context, working_set)
+            }
+            CallMessage::Unsubscribe { sub_address } => {
+                self.unsubscribe(SubAddress::new(&sub_address), context, working_set)
+            }
+            CallMessage::CreateComment {
+                post_address,
+                parent_comment,
+                content,
+            } => self.create_new_comment(
+                PostAddress::new(&post_address),
+                parent_comment.map(|parent| CommentAddress::new(&parent)),
+                &content,
+                context,
+                working_set,
+            ),
+            CallMessage::EditComment {
+                comment_address,
+                content,
+            } => self.edit_comment(CommentAddress::new(&comment_address), &content, context, working_set),
+            CallMessage::DeleteComment { comment_address } => {
+                self.delete_comment(CommentAddress::new(&comment_address), context, working_set)
+            }
+            CallMessage::Vote { post_address, direction } => {
+                self.vote_on_post(PostAddress::new(&post_address), direction, context, working_set)
+            }
         };
         Ok(call_result?)
     }
@@ -0,0 +1,207 @@
+use sov_modules_api::Context;
+
+use crate::address::UserAddress;
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize),
+    derive(schemars::JsonSchema),
+    derive(clap::ValueEnum)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone, Copy)]
+/// A single power that can be delegated to a moderator
+pub enum ModPermission {
+    MANAGE_POSTS,
+    MANAGE_USERS,
+    MANAGE_FLAIR,
+    MANAGE_SETTINGS,
+    MANAGE_MODS,
+}
+
+impl ModPermission {
+    pub fn all() -> Vec<ModPermission> {
+        vec![
+            ModPermission::MANAGE_POSTS,
+            ModPermission::MANAGE_USERS,
+            ModPermission::MANAGE_FLAIR,
+            ModPermission::MANAGE_SETTINGS,
+            ModPermission::MANAGE_MODS,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModPermission::MANAGE_POSTS => "MANAGE_POSTS",
+            ModPermission::MANAGE_USERS => "MANAGE_USERS",
+            ModPermission::MANAGE_FLAIR => "MANAGE_FLAIR",
+            ModPermission::MANAGE_SETTINGS => "MANAGE_SETTINGS",
+            ModPermission::MANAGE_MODS => "MANAGE_MODS",
+        }
+    }
+}
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone)]
+/// Moderator entry of a subreddit
+pub struct Moderator<C: Context> {
+    user_address: UserAddress<C>,
+    permissions: Vec<ModPermission>,
+}
+
+impl<C: Context> Moderator<C> {
+    pub fn new(user_address: UserAddress<C>, permissions: &[ModPermission]) -> Self {
+        let mut moderator = Moderator {
+            user_address,
+            permissions: vec![],
+        };
+        moderator.set_permissions(permissions);
+        moderator
+    }
+
+    #[allow(dead_code)]
+    pub fn get_user_address(&self) -> &UserAddress<C> {
+        &self.user_address
+    }
+
+    #[allow(dead_code)]
+    pub fn get_permissions(&self) -> &Vec<ModPermission> {
+        &self.permissions
+    }
+
+    pub fn has_permission(&self, permission: ModPermission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
+    /// Duplicates are dropped so the stored set stays canonical
+    pub(crate) fn set_permissions(&mut self, permissions: &[ModPermission]) {
+        self.permissions = ModPermission::all()
+            .into_iter()
+            .filter(|p| permissions.contains(p))
+            .collect();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::StateMapAccessor;
+
+    use super::ModPermission;
+    use crate::address::UserAddress;
+    use crate::test_utils::{error_code, CallResult, Fixture, Member, C};
+
+    fn add(f: &mut Fixture, by: &Member, user: &Member, permissions: &[ModPermission]) -> CallResult {
+        f.reddit
+            .add_moderator(f.sub.clone(), user.1.clone(), permissions.to_vec(), &by.0, &mut f.working_set)
+    }
+
+    fn remove(f: &mut Fixture, by: &Member, user: &Member) -> CallResult {
+        f.reddit.remove_moderator(f.sub.clone(), user.1.clone(), &by.0, &mut f.working_set)
+    }
+
+    fn set_permissions(f: &mut Fixture, by: &Member, user: &Member, permissions: &[ModPermission]) -> CallResult {
+        f.reddit
+            .set_moderator_permissions(f.sub.clone(), user.1.clone(), permissions.to_vec(), &by.0, &mut f.working_set)
+    }
+
+    fn mods(f: &mut Fixture) -> Vec<UserAddress<C>> {
+        f.reddit.sub_collections.get(&f.sub, &mut f.working_set).unwrap().get_mod_addresses()
+    }
+
+    #[test]
+    fn moderators_are_ordered_by_seniority() {
+        let mut f = Fixture::new();
+        let (owner, senior, junior) = (f.owner.clone(), f.user("senior"), f.user("junior"));
+
+        add(&mut f, &owner, &senior, &[ModPermission::MANAGE_MODS, ModPermission::MANAGE_POSTS]).unwrap();
+        add(&mut f, &senior, &junior, &[ModPermission::MANAGE_POSTS]).unwrap();
+
+        assert_eq!(mods(&mut f), vec![owner.1, senior.1, junior.1]);
+    }
+
+    #[test]
+    fn moderators_only_grant_permissions_they_hold() {
+        let mut f = Fixture::new();
+        let (owner, senior, junior) = (f.owner.clone(), f.user("senior"), f.user("junior"));
+        add(&mut f, &owner, &senior, &[ModPermission::MANAGE_MODS]).unwrap();
+
+        let granted = add(&mut f, &senior, &junior, &[ModPermission::MANAGE_SETTINGS]);
+        assert_eq!(error_code(granted), "MISSING_PERMISSION");
+
+        add(&mut f, &senior, &junior, &[ModPermission::MANAGE_MODS]).unwrap();
+        let raised = set_permissions(&mut f, &senior, &junior, &[ModPermission::MANAGE_MODS, ModPermission::MANAGE_FLAIR]);
+        assert_eq!(error_code(raised), "MISSING_PERMISSION");
+    }
+
+    #[test]
+    fn managing_moderators_needs_manage_mods() {
+        let mut f = Fixture::new();
+        let (owner, moderator, user) = (f.owner.clone(), f.user("moderator"), f.user("someone"));
+        add(&mut f, &owner, &moderator, &[ModPermission::MANAGE_POSTS]).unwrap();
+
+        assert_eq!(error_code(add(&mut f, &moderator, &user, &[])), "MISSING_PERMISSION");
+        assert_eq!(error_code(add(&mut f, &user, &moderator, &[])), "NOT_MODERATOR");
+    }
+
+    #[test]
+    fn only_junior_moderators_can_be_removed_or_changed() {
+        let mut f = Fixture::new();
+        let (owner, senior, junior) = (f.owner.clone(), f.user("senior"), f.user("junior"));
+        add(&mut f, &owner, &senior, &[ModPermission::MANAGE_MODS]).unwrap();
+        add(&mut f, &senior, &junior, &[ModPermission::MANAGE_MODS]).unwrap();
+
+        assert_eq!(error_code(remove(&mut f, &junior, &senior)), "NOT_SENIOR_MODERATOR");
+        assert_eq!(error_code(remove(&mut f, &senior, &owner)), "NOT_SENIOR_MODERATOR");
+        assert_eq!(error_code(set_permissions(&mut f, &senior, &senior, &[])), "NOT_SENIOR_MODERATOR");
+
+        remove(&mut f, &senior, &junior).unwrap();
+        remove(&mut f, &owner, &senior).unwrap();
+        assert_eq!(mods(&mut f), vec![owner.1]);
+    }
+
+    #[test]
+    fn owner_cannot_leave_before_transferring_ownership() {
+        let mut f = Fixture::new();
+        let (owner, successor, user) = (f.owner.clone(), f.user("successor"), f.user("someone"));
+
+        let left = f.reddit.leave_moderator(f.sub.clone(), &owner.0, &mut f.working_set);
+        assert_eq!(error_code(left), "OWNER_CANNOT_LEAVE");
+
+        let transferred = f.reddit.transfer_ownership(f.sub.clone(), user.1.clone(), &owner.0, &mut f.working_set);
+        assert_eq!(error_code(transferred), "TARGET_NOT_MODERATOR");
+
+        add(&mut f, &owner, &successor, &[]).unwrap();
+        f.reddit.transfer_ownership(f.sub.clone(), successor.1.clone(), &owner.0, &mut f.working_set).unwrap();
+        f.reddit.leave_moderator(f.sub.clone(), &owner.0, &mut f.working_set).unwrap();
+
+        let sub = f.reddit.sub_collections.get(&f.sub, &mut f.working_set).unwrap();
+        assert_eq!(sub.get_owner(), &successor.1);
+        assert!(!sub.is_moderator(&owner.1));
+    }
+
+    #[test]
+    fn moderators_with_manage_posts_remove_comments() {
+        let mut f = Fixture::new();
+        let (owner, flair_mod, post_mod, author) =
+            (f.owner.clone(), f.user("flair_mod"), f.user("post_mod"), f.user("author"));
+        add(&mut f, &owner, &flair_mod, &[ModPermission::MANAGE_FLAIR]).unwrap();
+        add(&mut f, &owner, &post_mod, &[ModPermission::MANAGE_POSTS]).unwrap();
+
+        let post = f.post(&author);
+        f.reddit.create_new_comment(post.clone(), None, "comment", &author.0, &mut f.working_set).unwrap();
+        let comment = f.reddit.post_comment_collections.get(&post, &mut f.working_set).unwrap()[0].clone();
+
+        let removed = f.reddit.delete_comment(comment.clone(), &flair_mod.0, &mut f.working_set);
+        assert_eq!(error_code(removed), "NOT_AUTHOR_OR_MODERATOR");
+
+        f.reddit.delete_comment(comment.clone(), &post_mod.0, &mut f.working_set).unwrap();
+        assert!(f.reddit.comment_collections.get(&comment, &mut f.working_set).unwrap().is_deleted());
+    }
+}
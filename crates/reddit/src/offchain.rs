@@ -1,18 +1,43 @@
-
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sov_modules_api::Context;
 use sov_modules_macros::offchain;
-use std::{sync::{Arc, Mutex, OnceLock}, time::Duration};
+use std::{cell::RefCell, collections::BTreeMap, fs::{File, OpenOptions}, io::{BufWriter, Write}, path::{Path, PathBuf}, sync::{mpsc, Mutex, OnceLock}, thread::{self, ThreadId}, time::Duration};
+use tracing::error;
+
+use crate::{event::RedditEvent, indexer::Indexer, journal::{SlotJournal, SlotStart}, outbox::Outbox, signature};
 
 
 
 static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
 
-#[derive(Serialize , Deserialize)]
+thread_local! {
+    /// Hash of the reddit transaction being executed on this thread, set by the pre dispatch hook
+    static CURRENT_TX_HASH: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Changes of the slot being executed, published by [`commit_slot`] once the slot is final
+static SLOT_BUFFER: Mutex<Option<SlotBuffer>> = Mutex::new(None);
+
+/// Version of the [`RedditChangeEnvelope`] layout, bumped on breaking changes:
+/// - 2: `PostArchived` events
+/// - 3: subreddit, post and comment events carry their `description`, `title`, `flair` and `content`
+/// - 4: `SubredditArchived` events
+/// - 5: `ModeratorAdded` events carry the granted `permissions`
+pub const SCHEMA_VERSION: u32 = 5;
+
+/// Sink installed by the node at startup, changes are dropped until then
+static SINK: OnceLock<Box<dyn OffchainSink>> = OnceLock::new();
+
+/// Recently published slots, loaded from disk by [`init_sink`] when a sink is configured
+static JOURNAL: OnceLock<Mutex<SlotJournal>> = OnceLock::new();
+
+#[derive(Serialize , Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RedditCollections {
     USER,
     SUBREDDIT,
     POST,
+    COMMENT,
 }
 
 
@@ -24,6 +49,7 @@ impl RedditCollections {
             RedditCollections::USER => "USER".to_string(),
             RedditCollections::SUBREDDIT => "SUBREDDIT".to_string(),
             RedditCollections::POST => "POST".to_string(),
+            RedditCollections::COMMENT => "COMMENT".to_string(),
         }
     }
 
@@ -33,16 +59,19 @@ impl RedditCollections {
             "USER" => Ok(RedditCollections::USER),
             "SUBREDDIT" => Ok(RedditCollections::SUBREDDIT),
             "POST" => Ok(RedditCollections::POST),
+            "COMMENT" => Ok(RedditCollections::COMMENT),
             _ => Err(format!("Unknown Collection: {}", s)),
         }
     }
 
 }
 
-#[derive(Serialize , Deserialize)]
+#[derive(Serialize , Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChangeType {
     CREATED,
-    UPDATED
+    UPDATED,
+    /// Cancels the earlier change with the same sequence, its slot is no longer part of the chain
+    REVERTED
 }
 
 
@@ -53,6 +82,7 @@ impl ChangeType {
         match self {
             ChangeType::CREATED => "CREATED".to_string(),
             ChangeType::UPDATED => "UPDATED".to_string(),
+            ChangeType::REVERTED => "REVERTED".to_string(),
         }
     }
 
@@ -61,6 +91,7 @@ impl ChangeType {
         match s {
             "CREATED" => Ok(ChangeType::CREATED),
             "UPDATED" => Ok(ChangeType::UPDATED),
+            "REVERTED" => Ok(ChangeType::REVERTED),
             _ => Err(format!("Unknown Changetype: {}", s)),
         }
     }
@@ -69,15 +100,268 @@ impl ChangeType {
 
 
 
-#[derive(Serialize , Deserialize)]
+#[derive(Serialize , Deserialize, Debug, Clone)]
 pub struct RedditStateChanges {
     pub state: RedditCollections,
+    /// JSON encoded [`RedditEvent`] describing the change
     pub change: String,
+    /// Address of the changed record in `state`
     pub address: String,
     pub change_type: ChangeType
 
 }
 
+impl RedditStateChanges {
+    /// Maps an event to the record it changed, moderation and subscription events update their subreddit
+    pub fn from_event<C: Context>(event: &RedditEvent<C>) -> Self {
+        let (state, address, change_type) = match event {
+            RedditEvent::UserCreated { user_address, .. } => {
+                (RedditCollections::USER, user_address.to_string(), ChangeType::CREATED)
+            }
+            RedditEvent::SubredditCreated { sub_address, .. } => {
+                (RedditCollections::SUBREDDIT, sub_address.to_string(), ChangeType::CREATED)
+            }
+            RedditEvent::SubredditSettingsUpdated { sub_address }
+            | RedditEvent::SubredditArchived { sub_address, .. }
+            | RedditEvent::ModeratorAdded { sub_address, .. }
+            | RedditEvent::ModeratorPermissionsUpdated { sub_address, .. }
+            | RedditEvent::ModeratorRemoved { sub_address, .. }
+            | RedditEvent::OwnershipTransferred { sub_address, .. }
+            | RedditEvent::UserBanned { sub_address, .. }
+            | RedditEvent::UserUnbanned { sub_address, .. }
+            | RedditEvent::UserMuted { sub_address, .. }
+            | RedditEvent::BanAppealed { sub_address, .. }
+            | RedditEvent::AppealDismissed { sub_address, .. }
+            | RedditEvent::Subscribed { sub_address, .. }
+            | RedditEvent::Unsubscribed { sub_address, .. } => {
+                (RedditCollections::SUBREDDIT, sub_address.to_string(), ChangeType::UPDATED)
+            }
+            RedditEvent::PostCreated { post_address, .. } => {
+                (RedditCollections::POST, post_address.to_string(), ChangeType::CREATED)
+            }
+            RedditEvent::PostEdited { post_address, .. }
+            | RedditEvent::PostDeleted { post_address, .. }
+            | RedditEvent::PostArchived { post_address }
+            | RedditEvent::PostVoted { post_address, .. } => {
+                (RedditCollections::POST, post_address.to_string(), ChangeType::UPDATED)
+            }
+            RedditEvent::CommentCreated { comment_address, .. } => {
+                (RedditCollections::COMMENT, comment_address.to_string(), ChangeType::CREATED)
+            }
+            RedditEvent::CommentEdited { comment_address, .. }
+            | RedditEvent::CommentDeleted { comment_address } => {
+                (RedditCollections::COMMENT, comment_address.to_string(), ChangeType::UPDATED)
+            }
+        };
+
+        RedditStateChanges {
+            state,
+            change: serde_json::to_string(event).expect("RedditEvent serialization cannot fail"),
+            address,
+            change_type,
+        }
+    }
+}
+
+
+#[derive(Serialize , Deserialize, Debug, Clone)]
+/// What sinks actually receive: a change plus where it happened in the chain.
+/// `sequence` increases by one per change across the whole rollup, so receivers can spot gaps and duplicates.
+/// After a reorg the new chain reuses the sequence numbers of the reverted changes
+pub struct RedditChangeEnvelope {
+    pub schema_version: u32,
+    pub sequence: u64,
+    pub slot_height: u64,
+    /// `0x` prefixed hash of the DA block the change was executed in
+    pub slot_hash: Option<String>,
+    /// `0x` prefixed hash of the transaction that made the change
+    pub tx_hash: Option<String>,
+    pub changes: RedditStateChanges,
+}
+
+impl RedditChangeEnvelope {
+    pub fn new(sequence: u64, slot_height: u64, changes: RedditStateChanges) -> Self {
+        RedditChangeEnvelope {
+            schema_version: SCHEMA_VERSION,
+            sequence,
+            slot_height,
+            slot_hash: JOURNAL
+                .get()
+                .and_then(|journal| journal.lock().expect("Journal lock poisoned").current_hash().map(String::from)),
+            tx_hash: CURRENT_TX_HASH.with(|tx_hash| tx_hash.borrow().clone()),
+            changes,
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// Where the node sends reddit state changes, read from the `[reddit_offchain]` table of the rollup config:
+///
+/// ```toml
+/// [reddit_offchain]
+/// type = "webhook"
+/// url = "http://localhost:8080/reddit"
+/// ```
+pub enum OffchainSinkConfig {
+    #[default]
+    Disabled,
+    /// POSTs every change as JSON to `url` through the durable [`Outbox`],
+    /// a change is dead-lettered after `max_attempts` failed deliveries.
+    /// With a `secret` every request carries a [`signature::SIGNATURE_HEADER`]
+    Webhook {
+        url: String,
+        #[serde(default = "default_max_attempts")]
+        max_attempts: u32,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+    /// Appends every change as one JSON line to `path`
+    JsonlFile { path: PathBuf },
+    Stdout,
+    /// Keeps a relational copy of users, subreddits, posts, comments and votes in the SQLite database at `path`
+    Sqlite { path: PathBuf },
+}
+
+
+/// Receives the committed reddit state changes of the node, one call per slot. Called from the finalize
+/// hook, so implementations must return without doing IO: sinks that write somewhere hand the changes
+/// to a [`SinkWriter`]. The only blocking write left in the slot execution is the journal append
+pub trait OffchainSink: Send + Sync {
+    fn publish(&self, envelopes: Vec<RedditChangeEnvelope>);
+}
+
+
+/// Blocking half of a sink, driven by the background thread of a [`SinkWriter`]
+pub trait ChangeWriter: Send + 'static {
+    fn write(&mut self, envelope: &RedditChangeEnvelope) -> anyhow::Result<()>;
+
+    /// Called after every batch, the changes written so far must be durable once it returns
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+
+const WRITER_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const WRITER_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Queues the published changes for a [`ChangeWriter`] running on its own thread.
+/// A change that fails to be written is retried until it succeeds and holds back every change
+/// behind it, so the sink never skips one. The queue is unbounded while the writer is stuck
+pub struct SinkWriter {
+    sender: Mutex<mpsc::Sender<Vec<RedditChangeEnvelope>>>,
+}
+
+impl SinkWriter {
+    pub fn spawn<W: ChangeWriter>(name: &str, mut writer: W) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Vec<RedditChangeEnvelope>>();
+
+        let thread_name = name.to_string();
+        thread::Builder::new()
+            .name(thread_name.clone())
+            .spawn(move || {
+                for envelopes in receiver {
+                    for envelope in &envelopes {
+                        retry(&thread_name, || writer.write(envelope));
+                    }
+                    retry(&thread_name, || writer.flush());
+                }
+            })?;
+
+        Ok(SinkWriter { sender: Mutex::new(sender) })
+    }
+}
+
+impl OffchainSink for SinkWriter {
+    fn publish(&self, envelopes: Vec<RedditChangeEnvelope>) {
+        if self.sender.lock().expect("Sink writer lock poisoned").send(envelopes).is_err() {
+            error!("Reddit offchain writer has stopped, changes are no longer published");
+        }
+    }
+}
+
+fn retry(name: &str, mut operation: impl FnMut() -> anyhow::Result<()>) {
+    let mut backoff = WRITER_INITIAL_BACKOFF;
+    while let Err(e) = operation() {
+        error!("Reddit offchain writer {} failed, retrying in {:?}: {}", name, backoff, e);
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(WRITER_MAX_BACKOFF);
+    }
+}
+
+
+fn default_max_attempts() -> u32 {
+    20
+}
+
+
+pub struct JsonlFileSink {
+    file: BufWriter<File>,
+}
+
+impl JsonlFileSink {
+    pub fn open(path: &PathBuf) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonlFileSink { file: BufWriter::new(file) })
+    }
+}
+
+impl ChangeWriter for JsonlFileSink {
+    fn write(&mut self, envelope: &RedditChangeEnvelope) -> anyhow::Result<()> {
+        let line = serde_json::to_string(envelope)?;
+        writeln!(self.file, "{}", line)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        Ok(())
+    }
+}
+
+
+pub struct StdoutSink;
+
+impl ChangeWriter for StdoutSink {
+    fn write(&mut self, envelope: &RedditChangeEnvelope) -> anyhow::Result<()> {
+        let line = serde_json::to_string(envelope)?;
+        writeln!(std::io::stdout().lock(), "{}", line)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        std::io::stdout().lock().flush()?;
+        Ok(())
+    }
+}
+
+
+/// Installs the sink described by `config`, can only be called once per process.
+/// The webhook outbox lives under `storage_path` and must be started from within a tokio runtime
+pub fn init_sink(config: &OffchainSinkConfig, storage_path: &Path) -> anyhow::Result<()> {
+    let sink: Box<dyn OffchainSink> = match config {
+        OffchainSinkConfig::Disabled => return Ok(()),
+        OffchainSinkConfig::Webhook { url, max_attempts, secret } => {
+            let outbox = Outbox::open(storage_path, url.clone(), *max_attempts, secret.clone())?;
+            outbox.start()?;
+            Box::new(SinkWriter::spawn("reddit-outbox", outbox)?)
+        }
+        OffchainSinkConfig::JsonlFile { path } => Box::new(SinkWriter::spawn("reddit-jsonl", JsonlFileSink::open(path)?)?),
+        OffchainSinkConfig::Stdout => Box::new(SinkWriter::spawn("reddit-stdout", StdoutSink)?),
+        OffchainSinkConfig::Sqlite { path } => Box::new(SinkWriter::spawn("reddit-indexer", Indexer::open(path)?)?),
+    };
+
+    JOURNAL
+        .set(Mutex::new(SlotJournal::open(storage_path)?))
+        .map_err(|_| anyhow::anyhow!("Reddit offchain journal is already initialized"))?;
+    SINK.set(sink)
+        .map_err(|_| anyhow::anyhow!("Reddit offchain sink is already initialized"))
+}
+
+
 pub fn get_global_client() -> &'static Client {
     HTTP_CLIENT.get_or_init(|| {
         Client::builder()
@@ -91,17 +375,109 @@ pub fn get_global_client() -> &'static Client {
 }
 
 
+/// Signs `body` for the [`signature::SIGNATURE_HEADER`], shared by every webhook delivery
+pub(crate) fn sign_body(secret: &str, body: &[u8]) -> String {
+    signature::sign(secret.as_bytes(), body)
+}
+
+
+/// Envelopes emitted while a slot executes. Keyed by sequence: a reverted transaction or batch rolls
+/// `change_sequence` back, so the next change reuses the sequence and replaces the reverted envelope
+struct SlotBuffer {
+    /// Thread executing the slot, transactions simulated elsewhere (e.g. by the sequencer) are not buffered
+    thread: ThreadId,
+    height: u64,
+    envelopes: BTreeMap<u64, RedditChangeEnvelope>,
+    /// Value of `change_sequence` after the end slot hook, every envelope at or above it was reverted
+    next_sequence: Option<u64>,
+}
+
+
+/// Called for every DA slot before its transactions run. A slot that was already published
+/// is not published again, a different block at a published height reverts everything from there on
 #[offchain]
-pub async fn publish_state(
-    body: RedditStateChanges
+pub fn begin_slot(height: u64, hash: String) {
+    let (Some(journal), Some(sink)) = (JOURNAL.get(), SINK.get()) else {
+        return;
+    };
+
+    *SLOT_BUFFER.lock().expect("Slot buffer lock poisoned") = Some(SlotBuffer {
+        thread: thread::current().id(),
+        height,
+        envelopes: BTreeMap::new(),
+        next_sequence: None,
+    });
+
+    match journal.lock().expect("Journal lock poisoned").begin_slot(height, hash) {
+        Ok(SlotStart::Reorg(reverted)) => sink.publish(reverted),
+        Ok(SlotStart::New | SlotStart::Replay) => {}
+        Err(e) => error!("Reddit offchain journal failed to record the reorg at slot {}: {}", height, e),
+    }
+}
+
+
+#[offchain]
+pub fn set_current_tx_hash(tx_hash: Option<String>) {
+    CURRENT_TX_HASH.with(|current| *current.borrow_mut() = tx_hash);
+}
+
+
+/// Buffers a change of the executing slot until [`commit_slot`]
+#[offchain]
+pub fn publish_state(
+    body: RedditChangeEnvelope
 )  {
+    if SINK.get().is_none() {
+        return;
+    }
 
+    let mut buffer = SLOT_BUFFER.lock().expect("Slot buffer lock poisoned");
+    if let Some(slot) = buffer.as_mut().filter(|slot| slot.thread == thread::current().id()) {
+        slot.envelopes.insert(body.sequence, body);
+    }
+}
 
-    tokio::spawn( async move {
-        let payload_string = serde_json::to_string_pretty(&body).unwrap();
-            let _ = get_global_client().post("random_url").body(payload_string).send().await;
-    });
 
+/// Called by the end slot hook with the final `change_sequence` of the slot
+#[offchain]
+pub fn end_slot(next_sequence: u64) {
+    let mut buffer = SLOT_BUFFER.lock().expect("Slot buffer lock poisoned");
+    if let Some(slot) = buffer.as_mut().filter(|slot| slot.thread == thread::current().id()) {
+        slot.next_sequence = Some(next_sequence);
+    }
+}
 
 
-}
\ No newline at end of file
+/// Publishes the changes that survived the slot, called by the finalize hook once nothing can revert them anymore
+#[offchain]
+pub fn commit_slot() {
+    let Some(slot) = SLOT_BUFFER.lock().expect("Slot buffer lock poisoned").take() else {
+        return;
+    };
+    let Some(next_sequence) = slot.next_sequence else {
+        return;
+    };
+    let slot_height = slot.height;
+    let committed: Vec<_> = slot
+        .envelopes
+        .into_values()
+        .filter(|envelope| envelope.sequence < next_sequence)
+        .collect();
+
+    let (Some(journal), Some(sink)) = (JOURNAL.get(), SINK.get()) else {
+        return;
+    };
+    {
+        let mut journal = journal.lock().expect("Journal lock poisoned");
+        if journal.is_replaying() {
+            return;
+        }
+        // Still published. The slot is published again if it is executed after a restart, the outbox and
+        // the indexer skip changes they already have, the JSONL and stdout sinks deliver them at least once
+        if let Err(e) = journal.commit_slot(&committed) {
+            error!("Reddit offchain journal failed to record slot {}: {}", slot_height, e);
+        }
+    }
+
+    sink.publish(committed);
+}
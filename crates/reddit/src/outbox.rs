@@ -0,0 +1,469 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fs, io, path::{Path, PathBuf}, sync::{Arc, Mutex, OnceLock}, time::{Duration, SystemTime, UNIX_EPOCH}};
+use tokio::sync::Notify;
+use tracing::error;
+
+use crate::{offchain::{get_global_client, sign_body, ChangeType, ChangeWriter, RedditChangeEnvelope, SCHEMA_VERSION}, signature::{SCHEMA_VERSION_HEADER, SIGNATURE_HEADER}};
+
+
+/// Directory created inside the node's storage path
+pub const OUTBOX_DIR: &str = "reddit-outbox";
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The running outbox of this node, exposed for the status RPC
+static OUTBOX: OnceLock<Arc<Outbox>> = OnceLock::new();
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A change waiting for delivery, or one that gave up after `max_attempts`
+pub struct OutboxEntry {
+    pub sequence: u64,
+    /// Unix seconds at which the change was recorded
+    pub enqueued_at: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub envelope: RedditChangeEnvelope,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OutboxStatus {
+    pub pending: u64,
+    pub oldest_pending_sequence: Option<u64>,
+    /// Seconds the oldest pending change has been waiting
+    pub lag_secs: u64,
+    pub last_delivered_sequence: Option<u64>,
+    pub dead_letters: Vec<OutboxEntry>,
+    /// Last failed disk operation, cleared by the next one that succeeds. New changes are held
+    /// back by the sink writer while the outbox cannot persist them
+    pub disk_error: Option<String>,
+}
+
+
+/// Disk backed FIFO of state changes delivered to a webhook one at a time.
+/// Every change is written to `pending/` before `enqueue` returns and only removed once
+/// the webhook acknowledged it, entries that keep failing are moved to `dead/`.
+///
+/// Changes reach the outbox in change sequence order. The outbox persists the next change it
+/// expects and skips the ones below it, so a slot published again after a restart is not
+/// delivered twice. A REVERTED change moves that cursor back to the sequence it cancels, the
+/// new branch then reuses the sequences from there on
+pub struct Outbox {
+    url: String,
+    max_attempts: u32,
+    secret: Option<String>,
+    pending_dir: PathBuf,
+    dead_dir: PathBuf,
+    sequence_path: PathBuf,
+    state: Mutex<OutboxState>,
+    notify: Notify,
+}
+
+struct OutboxState {
+    next_sequence: u64,
+    /// Change sequence the next CREATED or UPDATED change must have to be enqueued
+    next_change: u64,
+    pending: VecDeque<u64>,
+    last_delivered: Option<u64>,
+    disk_error: Option<String>,
+}
+
+
+impl Outbox {
+    /// Opens the outbox under `storage_path`, picking up changes left over from a previous run
+    pub fn open(storage_path: &Path, url: String, max_attempts: u32, secret: Option<String>) -> anyhow::Result<Arc<Self>> {
+        let root = storage_path.join(OUTBOX_DIR);
+        let pending_dir = root.join("pending");
+        let dead_dir = root.join("dead");
+        fs::create_dir_all(&pending_dir)?;
+        fs::create_dir_all(&dead_dir)?;
+
+        // Holds the next position and the next change, files written before the change cursor only have the position
+        let sequence_path = root.join("next_sequence");
+        let (next_sequence, next_change) = match fs::read_to_string(&sequence_path) {
+            Ok(content) => {
+                let mut fields = content.split_whitespace();
+                let next_sequence = fields.next().unwrap_or("0").parse()?;
+                let next_change = fields.next().map(str::parse::<u64>).transpose()?.unwrap_or(0);
+                (next_sequence, next_change)
+            }
+            Err(_) => (0, 0),
+        };
+
+        let mut pending: Vec<u64> = list_sequences(&pending_dir)?;
+        pending.sort_unstable();
+
+        Ok(Arc::new(Outbox {
+            url,
+            max_attempts,
+            secret,
+            pending_dir,
+            dead_dir,
+            sequence_path,
+            state: Mutex::new(OutboxState {
+                next_sequence,
+                next_change,
+                pending: pending.into(),
+                last_delivered: None,
+                disk_error: None,
+            }),
+            notify: Notify::new(),
+        }))
+    }
+
+    /// Makes this the outbox reported by [`status`] and starts delivering on the current tokio runtime
+    pub fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        OUTBOX
+            .set(self.clone())
+            .map_err(|_| anyhow::anyhow!("Reddit outbox is already running"))?;
+        tokio::spawn(self.clone().run());
+        Ok(())
+    }
+
+    /// Persists `envelope` and returns its position in the outbox, or `None` when the change was
+    /// already enqueued. A failure is logged and reported by [`Outbox::status`] until the disk
+    /// accepts writes again
+    pub fn enqueue(&self, envelope: RedditChangeEnvelope) -> anyhow::Result<Option<u64>> {
+        let mut state = self.state.lock().expect("Outbox lock poisoned");
+        let sequence = state.next_sequence;
+        let change = envelope.sequence;
+
+        // A revert is only delivered for a change that was enqueued, the others are replays
+        let reverted = envelope.changes.change_type == ChangeType::REVERTED;
+        if reverted != (change < state.next_change) {
+            return Ok(None);
+        }
+        let next_change = if reverted { change } else { change + 1 };
+
+        let entry = OutboxEntry {
+            sequence,
+            enqueued_at: unix_now(),
+            attempts: 0,
+            last_error: None,
+            envelope,
+        };
+        let persisted = serde_json::to_vec(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| write_atomic(&entry_path(&self.pending_dir, sequence), &bytes))
+            .and_then(|()| write_atomic(&self.sequence_path, format!("{} {}", sequence + 1, next_change).as_bytes()));
+        if let Err(e) = persisted {
+            error!("Reddit outbox failed to persist change {}: {}", change, e);
+            state.disk_error = Some(format!("Failed to persist change {}: {}", change, e));
+            return Err(e);
+        }
+
+        state.next_sequence = sequence + 1;
+        state.next_change = next_change;
+        state.pending.push_back(sequence);
+        state.disk_error = None;
+        drop(state);
+
+        self.notify.notify_one();
+        Ok(Some(sequence))
+    }
+
+    pub fn status(&self) -> anyhow::Result<OutboxStatus> {
+        let (pending, oldest, last_delivered, disk_error) = {
+            let state = self.state.lock().expect("Outbox lock poisoned");
+            (state.pending.len() as u64, state.pending.front().copied(), state.last_delivered, state.disk_error.clone())
+        };
+
+        let lag_secs = match oldest {
+            Some(sequence) => unix_now().saturating_sub(read_entry(&self.pending_dir, sequence)?.enqueued_at),
+            None => 0,
+        };
+
+        let mut dead_sequences = list_sequences(&self.dead_dir)?;
+        dead_sequences.sort_unstable();
+        let dead_letters = dead_sequences
+            .into_iter()
+            .map(|sequence| read_entry(&self.dead_dir, sequence))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(OutboxStatus {
+            pending,
+            oldest_pending_sequence: oldest,
+            lag_secs,
+            last_delivered_sequence: last_delivered,
+            dead_letters,
+            disk_error,
+        })
+    }
+
+    /// Delivers pending changes strictly in order, a failing change blocks the ones behind it.
+    /// Disk operations run on the blocking pool and are retried until they succeed
+    async fn run(self: Arc<Self>) {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            let head = self.state.lock().expect("Outbox lock poisoned").pending.front().copied();
+            let Some(sequence) = head else {
+                self.notify.notified().await;
+                continue;
+            };
+
+            let pending_dir = self.pending_dir.clone();
+            let entry = self
+                .with_disk("read a pending change", move || match read_entry(&pending_dir, sequence) {
+                    Ok(entry) => Ok(Some(entry)),
+                    Err(e) if is_not_found(&e) => Ok(None),
+                    Err(e) => Err(e),
+                })
+                .await;
+            let Some(mut entry) = entry else {
+                // Removed by hand, nothing left to deliver
+                self.pop(sequence, false);
+                continue;
+            };
+
+            match self.deliver(&entry).await {
+                Ok(()) => {
+                    let pending_dir = self.pending_dir.clone();
+                    self.with_disk("remove a delivered change", move || remove_entry(&pending_dir, sequence))
+                        .await;
+                    self.pop(sequence, true);
+                    backoff = INITIAL_BACKOFF;
+                }
+                Err(error) => {
+                    entry.attempts += 1;
+                    entry.last_error = Some(error);
+                    let bytes = serde_json::to_vec(&entry).expect("OutboxEntry serialization cannot fail");
+
+                    if entry.attempts >= self.max_attempts {
+                        let (pending_dir, dead_dir) = (self.pending_dir.clone(), self.dead_dir.clone());
+                        self.with_disk("move a change to the dead letters", move || {
+                            write_atomic(&entry_path(&dead_dir, sequence), &bytes)?;
+                            remove_entry(&pending_dir, sequence)
+                        })
+                        .await;
+                        self.pop(sequence, false);
+                        backoff = INITIAL_BACKOFF;
+                    } else {
+                        let pending_dir = self.pending_dir.clone();
+                        self.with_disk("record a failed delivery", move || {
+                            write_atomic(&entry_path(&pending_dir, sequence), &bytes)
+                        })
+                        .await;
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs `operation` on the blocking pool until it succeeds, every failure is logged and
+    /// shows up in the status until the disk recovers
+    async fn with_disk<T, F>(&self, what: &str, operation: F) -> T
+    where
+        T: Send + 'static,
+        F: Fn() -> anyhow::Result<T> + Clone + Send + 'static,
+    {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            let result = tokio::task::spawn_blocking(operation.clone())
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result);
+
+            match result {
+                Ok(value) => {
+                    self.state.lock().expect("Outbox lock poisoned").disk_error = None;
+                    return value;
+                }
+                Err(e) => {
+                    error!("Reddit outbox failed to {}, retrying in {:?}: {}", what, backoff, e);
+                    self.state.lock().expect("Outbox lock poisoned").disk_error = Some(format!("Failed to {}: {}", what, e));
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    async fn deliver(&self, entry: &OutboxEntry) -> Result<(), String> {
+        let payload_string = serde_json::to_string(&entry.envelope).map_err(|e| e.to_string())?;
+        let mut request = get_global_client()
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .header(SCHEMA_VERSION_HEADER, SCHEMA_VERSION.to_string());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign_body(secret, payload_string.as_bytes()));
+        }
+
+        let response = request
+            .body(payload_string)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Webhook responded with status {}", response.status()))
+        }
+    }
+
+    fn pop(&self, sequence: u64, delivered: bool) {
+        let mut state = self.state.lock().expect("Outbox lock poisoned");
+        if state.pending.front() == Some(&sequence) {
+            state.pending.pop_front();
+        }
+        if delivered {
+            state.last_delivered = Some(sequence);
+        }
+    }
+}
+
+
+impl ChangeWriter for Arc<Outbox> {
+    /// Runs on the outbox's [`crate::offchain::SinkWriter`] thread, which keeps retrying a failed enqueue
+    fn write(&mut self, envelope: &RedditChangeEnvelope) -> anyhow::Result<()> {
+        self.enqueue(envelope.clone()).map(|_| ())
+    }
+}
+
+
+/// Status of the node's outbox, `None` when no webhook is configured
+pub fn status() -> Option<anyhow::Result<OutboxStatus>> {
+    OUTBOX.get().map(|outbox| outbox.status())
+}
+
+
+fn entry_path(dir: &Path, sequence: u64) -> PathBuf {
+    // Zero padded so the directory listing sorts in delivery order
+    dir.join(format!("{:020}.json", sequence))
+}
+
+fn read_entry(dir: &Path, sequence: u64) -> anyhow::Result<OutboxEntry> {
+    Ok(serde_json::from_slice(&fs::read(entry_path(dir, sequence))?)?)
+}
+
+fn list_sequences(dir: &Path) -> anyhow::Result<Vec<u64>> {
+    let mut sequences = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let name = file?.file_name();
+        if let Some(sequence) = name.to_str().and_then(|name| name.strip_suffix(".json")) {
+            if let Ok(sequence) = sequence.parse() {
+                sequences.push(sequence);
+            }
+        }
+    }
+    Ok(sequences)
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<io::Error>(), Some(e) if e.kind() == io::ErrorKind::NotFound)
+}
+
+fn remove_entry(dir: &Path, sequence: u64) -> anyhow::Result<()> {
+    match fs::remove_file(entry_path(dir, sequence)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    sync_dir(dir)
+}
+
+/// Writes through a temporary file so a crash never leaves a half written entry behind
+fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)?;
+    fs::File::open(&tmp)?.sync_all()?;
+    fs::rename(tmp, path)?;
+    // The rename itself is only durable once the directory is synced
+    sync_dir(path.parent().unwrap_or(Path::new(".")))
+}
+
+fn sync_dir(dir: &Path) -> anyhow::Result<()> {
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::envelope;
+
+    fn open(dir: &tempfile::TempDir) -> Arc<Outbox> {
+        Outbox::open(dir.path(), "http://localhost".to_string(), 3, None).unwrap()
+    }
+
+    fn reverted(sequence: u64, slot_height: u64) -> RedditChangeEnvelope {
+        let mut envelope = envelope(sequence, slot_height);
+        envelope.changes.change_type = ChangeType::REVERTED;
+        envelope
+    }
+
+    fn pending_changes(outbox: &Outbox) -> Vec<(u64, ChangeType)> {
+        let state = outbox.state.lock().unwrap();
+        state
+            .pending
+            .iter()
+            .map(|sequence| read_entry(&outbox.pending_dir, *sequence).unwrap().envelope)
+            .map(|envelope| (envelope.sequence, envelope.changes.change_type))
+            .collect()
+    }
+
+    #[test]
+    fn a_replayed_slot_is_not_enqueued_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = open(&dir);
+        for sequence in 0..3 {
+            assert_eq!(outbox.enqueue(envelope(sequence, 1)).unwrap(), Some(sequence));
+        }
+        assert_eq!(outbox.enqueue(envelope(2, 1)).unwrap(), None);
+
+        // The node restarts and publishes the slot again
+        let outbox = open(&dir);
+        for sequence in 0..3 {
+            assert_eq!(outbox.enqueue(envelope(sequence, 1)).unwrap(), None);
+        }
+        assert_eq!(outbox.enqueue(envelope(3, 2)).unwrap(), Some(3));
+        assert_eq!(outbox.status().unwrap().pending, 4);
+    }
+
+    #[test]
+    fn a_reorg_enqueues_the_reverts_and_the_new_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = open(&dir);
+        for sequence in 0..3 {
+            outbox.enqueue(envelope(sequence, sequence + 1)).unwrap();
+        }
+
+        // Reverts of changes the outbox never had are skipped
+        assert_eq!(outbox.enqueue(reverted(3, 4)).unwrap(), None);
+        assert_eq!(outbox.enqueue(reverted(2, 3)).unwrap(), Some(3));
+        assert_eq!(outbox.enqueue(reverted(1, 2)).unwrap(), Some(4));
+        assert_eq!(outbox.enqueue(envelope(1, 2)).unwrap(), Some(5));
+
+        // Replaying the revert after a restart does not enqueue it again
+        let outbox = open(&dir);
+        assert_eq!(outbox.enqueue(reverted(1, 2)).unwrap(), None);
+        assert_eq!(outbox.enqueue(envelope(1, 2)).unwrap(), None);
+        assert_eq!(outbox.enqueue(envelope(2, 3)).unwrap(), Some(6));
+
+        assert_eq!(
+            pending_changes(&outbox),
+            vec![
+                (0, ChangeType::CREATED),
+                (1, ChangeType::CREATED),
+                (2, ChangeType::CREATED),
+                (2, ChangeType::REVERTED),
+                (1, ChangeType::REVERTED),
+                (1, ChangeType::CREATED),
+                (2, ChangeType::CREATED),
+            ]
+        );
+    }
+}
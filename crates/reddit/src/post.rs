@@ -1,6 +1,8 @@
-use sov_modules_api::{Context, WorkingSet};
+use std::fmt;
 
-use crate::{address::{PostAddress, SubAddress, UserAddress}, user::User, utils::get_post_address};
+use sov_modules_api::Context;
+
+use crate::{address::{PostAddress, SubAddress, UserAddress}, timestamp::SlotTime, utils::get_post_address, vote::VoteDirection};
 
 
 
@@ -18,10 +20,21 @@ pub struct Post<C: Context> {
     post_title: String,
     flair: String,
     content: String,
-    status: String,
+    status: PostStatus,
+    score: i64,
+    upvotes: u64,
+    downvotes: u64,
+    created_at: SlotTime,
+    last_edited_at: Option<SlotTime>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// Lifecycle of a post, deleted posts are kept as tombstones
 pub enum PostStatus{
     ACTIVE,
     ARCHIVED,
@@ -31,16 +44,16 @@ pub enum PostStatus{
 impl PostStatus {
 
 
-        fn to_string(&self) -> String {
+        pub fn as_str(&self) -> &'static str {
         match self {
-            PostStatus::ACTIVE => "ACTIVE".to_string(),
-            PostStatus::ARCHIVED => "ARCHIVED".to_string(),
-            PostStatus::DELETED => "DELETED".to_string(),
+            PostStatus::ACTIVE => "ACTIVE",
+            PostStatus::ARCHIVED => "ARCHIVED",
+            PostStatus::DELETED => "DELETED",
         }
     }
 
     // Convert string to enum
-    fn from_string(s: &str) -> Result<Self, String> {
+    pub fn from_string(s: &str) -> Result<Self, String> {
         match s {
             "ACTIVE" => Ok(PostStatus::ACTIVE),
             "ARCHIVED" => Ok(PostStatus::ARCHIVED),
@@ -51,32 +64,42 @@ impl PostStatus {
 
 }
 
+impl fmt::Display for PostStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl<C: Context> Post<C> {
     pub fn new(
         title: &str,
         flair: &str,
         content: &str,
         sub_address: SubAddress<C>,
-    context: &C,
-    working_set: &mut WorkingSet<C>
+        author: UserAddress<C>,
+        nonce: u64,
+        now: SlotTime,
  ) -> anyhow::Result<(PostAddress<C> , Post<C>)> {
 
 
-        let creator = context.sender();
-
-    let post_address = get_post_address(creator.as_ref() ,
-     sub_address.as_ref() );
+    let post_address = get_post_address(author.as_ref() ,
+     sub_address.as_ref(), nonce );
 
     Ok(  
 
         (post_address.clone() , Post {
         post_address,
-        user_address: UserAddress::new(creator),
+        user_address: author,
         subaddress: sub_address,
         post_title: title.to_string(),
         flair: flair.to_string(),
         content: content.to_string(),
-        status: PostStatus::ACTIVE.to_string(),
+        status: PostStatus::ACTIVE,
+        score: 0,
+        upvotes: 0,
+        downvotes: 0,
+        created_at: now,
+        last_edited_at: None,
     })
 
     )
@@ -118,8 +141,77 @@ impl<C: Context> Post<C> {
  } 
  
  #[allow(dead_code)]
- pub fn get_post_status(&self) -> &str {
-    &self.status
+ pub fn get_post_status(&self) -> PostStatus {
+    self.status
+ }
+
+ /// Only the content and the flair of a post can change, the title is immutable
+ pub(crate) fn edit(&mut self, content: Option<&str>, flair: Option<&str>, now: SlotTime) {
+    self.last_edited_at = Some(now);
+    if let Some(content) = content {
+        self.content = content.to_string();
+    }
+    if let Some(flair) = flair {
+        self.flair = flair.to_string();
+    }
+ }
+
+ #[allow(dead_code)]
+ pub fn get_created_at_slot(&self) -> u64 {
+    self.created_at.slot_height
+ }
+
+ #[allow(dead_code)]
+ pub fn get_created_at(&self) -> SlotTime {
+    self.created_at
+ }
+
+ #[allow(dead_code)]
+ pub fn get_last_edited_at(&self) -> Option<SlotTime> {
+    self.last_edited_at
+ }
+
+ pub(crate) fn mark_archived(&mut self) {
+    self.status = PostStatus::ARCHIVED;
+ }
+
+ /// Tombstones the post, the record stays so votes and comments keep pointing at it
+ pub(crate) fn mark_deleted(&mut self) {
+    self.content = String::new();
+    self.status = PostStatus::DELETED;
+ }
+
+ #[allow(dead_code)]
+ pub fn get_score(&self) -> i64 {
+    self.score
+ }
+
+ #[allow(dead_code)]
+ pub fn get_upvotes(&self) -> u64 {
+    self.upvotes
+ }
+
+ #[allow(dead_code)]
+ pub fn get_downvotes(&self) -> u64 {
+    self.downvotes
+ }
+
+ /// Replaces a previous vote of a user with a new one and returns the score delta
+ pub(crate) fn apply_vote(&mut self, previous: VoteDirection, next: VoteDirection) -> i64 {
+    match previous {
+        VoteDirection::UP => self.upvotes -= 1,
+        VoteDirection::DOWN => self.downvotes -= 1,
+        VoteDirection::NONE => {}
+    }
+    match next {
+        VoteDirection::UP => self.upvotes += 1,
+        VoteDirection::DOWN => self.downvotes += 1,
+        VoteDirection::NONE => {}
+    }
+
+    let delta = next.value() - previous.value();
+    self.score += delta;
+    delta
  }
 
 
@@ -1,18 +1,49 @@
-use crate::{address::{PostAddress, SubAddress, UserAddress}, utils::{get_post_address, get_sub_address, get_user_address}, Reddit};
-use sov_modules_api::{Context, StateMapAccessor, WorkingSet};
+use crate::{address::{CommentAddress, PostAddress, SubAddress, UserAddress}, ban::{Appeal, Ban}, comment::{Comment, MAX_COMMENT_DEPTH}, error::RedditError, moderator::Moderator, post::{Post, PostStatus}, subreddit::SubReddit, timestamp::SlotTime, user::User, ranking::{FeedKey, FeedSort, FeedWindow}, utils::{get_post_address, get_sub_address, get_user_address, normalize_subname, normalize_username}, vote::VoteDirection, Reddit};
+use sov_modules_api::{Context, StateMapAccessor, StateValueAccessor, WorkingSet};
 use sov_modules_macros::rpc_gen;
 use jsonrpsee::core::RpcResult;
 
 
+/// Maximum number of addresses accepted by the batch getters
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Page size of the list methods when no limit is given
+pub const DEFAULT_PAGE_SIZE: usize = 25;
+
+/// Largest page size accepted by the list methods
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Index positions a single list call looks at, a page that runs into many deleted
+/// posts comes back short with a cursor to continue from
+pub const MAX_SCANNED_POSITIONS: u64 = 500;
+
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(bound(
-    serialize = "UserAddress<C>: serde::Serialize",
-    deserialize = "UserAddress<C>: serde::Deserialize<'de>"
+    serialize = "UserAddress<C>: serde::Serialize, C::Address: serde::Serialize",
+    deserialize = "UserAddress<C>: serde::Deserialize<'de>, C::Address: serde::Deserialize<'de>"
 ))]
 /// Response for `getCollection` method
 pub struct UserCollectionResponse<C: Context> {
     pub username: String,
     pub user_address: UserAddress<C>,
+    pub owner: C::Address,
+    pub karma: i64,
+    pub created_at: SlotTime,
+    pub last_edited_at: Option<SlotTime>,
+}
+
+impl<C: Context> From<&User<C>> for UserCollectionResponse<C> {
+    fn from(u: &User<C>) -> Self {
+        UserCollectionResponse {
+            username: u.get_username().to_string(),
+            user_address: u.get_user_address(),
+            owner: u.get_owner().clone(),
+            karma: u.get_karma(),
+            created_at: u.get_created_at(),
+            last_edited_at: u.get_last_edited_at(),
+        }
+    }
 }
 
 
@@ -38,7 +69,65 @@ pub struct SubRedditCollectionResponse<C: Context> {
     pub subname: String,
     pub desription: String,
     pub subaddress: SubAddress<C>,
-    pub mods: Vec<UserAddress<C>>
+    pub owner: UserAddress<C>,
+    pub mods: Vec<UserAddress<C>>,
+    pub subscriber_count: u64,
+    /// Archived subreddits no longer accept posts
+    pub archived: bool,
+    pub created_at: SlotTime,
+    pub last_edited_at: Option<SlotTime>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "SubAddress<C>: serde::Serialize, Moderator<C>: serde::Serialize",
+    deserialize = "SubAddress<C>: serde::Deserialize<'de>, Moderator<C>: serde::Deserialize<'de>"
+))]
+/// Response for `getModerators` method
+pub struct ModeratorsResponse<C: Context> {
+    pub sub_address: SubAddress<C>,
+    pub owner: UserAddress<C>,
+    /// Ordered by seniority, starting with the owner
+    pub mods: Vec<Moderator<C>>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "SubAddress<C>: serde::Serialize, Ban<C>: serde::Serialize",
+    deserialize = "SubAddress<C>: serde::Deserialize<'de>, Ban<C>: serde::Deserialize<'de>"
+))]
+/// Response for `getBans` method, expired bans are only listed when asked for
+pub struct BansResponse<C: Context> {
+    pub sub_address: SubAddress<C>,
+    pub bans: Vec<Ban<C>>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "SubAddress<C>: serde::Serialize, Appeal<C>: serde::Serialize",
+    deserialize = "SubAddress<C>: serde::Deserialize<'de>, Appeal<C>: serde::Deserialize<'de>"
+))]
+/// Response for `getPendingAppeals` method
+pub struct AppealsResponse<C: Context> {
+    pub sub_address: SubAddress<C>,
+    /// Oldest first
+    pub appeals: Vec<Appeal<C>>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "UserAddress<C>: serde::Serialize, SubAddress<C>: serde::Serialize",
+    deserialize = "UserAddress<C>: serde::Deserialize<'de>, SubAddress<C>: serde::Deserialize<'de>"
+))]
+/// Response for `getSubscriptions` method
+pub struct SubscriptionsResponse<C: Context> {
+    pub user_address: UserAddress<C>,
+    /// In subscription order
+    pub subscriptions: Vec<SubAddress<C>>,
 }
 
 
@@ -68,8 +157,40 @@ pub struct PostCollectionResponse<C: Context> {
     pub post_title: String,
     pub content: String,
     pub flair: String,
-    pub status: String
-   
+    pub status: PostStatus,
+    pub score: i64,
+    pub upvotes: u64,
+    pub downvotes: u64,
+    pub created_at: SlotTime,
+    pub last_edited_at: Option<SlotTime>,
+    /// Vote of the `voter` passed to `getPost`, if any
+    pub my_vote: Option<VoteDirection>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "PostAddress<C>: serde::Serialize",
+    deserialize = "PostAddress<C>: serde::Deserialize<'de>"
+))]
+/// Response for `listSubredditPosts` and `listUserPosts`.
+/// Pass `next_cursor` back to get the following page, `None` means there are no older posts
+pub struct PostListResponse<C: Context> {
+    pub posts: Vec<PostCollectionResponse<C>>,
+    pub next_cursor: Option<u64>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "PostAddress<C>: serde::Serialize, FeedKey<C>: serde::Serialize",
+    deserialize = "PostAddress<C>: serde::Deserialize<'de>, FeedKey<C>: serde::Deserialize<'de>"
+))]
+/// Response for `getFeed`. `next_cursor` is the position of the last post looked at,
+/// posts that moved above it since are not repeated on the following page
+pub struct FeedResponse<C: Context> {
+    pub posts: Vec<PostCollectionResponse<C>>,
+    pub next_cursor: Option<FeedKey<C>>,
 }
 
 
@@ -79,8 +200,92 @@ pub struct PostCollectionResponse<C: Context> {
     deserialize = "PostAddress<C>: serde::Deserialize<'de>"
 ))]
 pub struct PostAddressResponse<C: Context> {
-    /// Address of the collection
-    pub sub_address: PostAddress<C>,
+    /// Address of the post
+    pub post_address: PostAddress<C>,
+}
+
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "CommentAddress<C>: serde::Serialize",
+    deserialize = "CommentAddress<C>: serde::Deserialize<'de>"
+))]
+pub struct CommentCollectionResponse<C: Context> {
+    pub comment_address: CommentAddress<C>,
+    pub post_address: PostAddress<C>,
+    pub user_address: UserAddress<C>,
+    pub parent_comment: Option<CommentAddress<C>>,
+    pub content: String,
+    pub depth: u32,
+    pub deleted: bool,
+}
+
+impl<C: Context> From<&Comment<C>> for CommentCollectionResponse<C> {
+    fn from(c: &Comment<C>) -> Self {
+        CommentCollectionResponse {
+            comment_address: c.get_comment_address().clone(),
+            post_address: c.get_post_address().clone(),
+            user_address: c.get_user_address().clone(),
+            parent_comment: c.get_parent_comment().cloned(),
+            content: c.get_content().to_string(),
+            depth: c.get_depth(),
+            deleted: c.is_deleted(),
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "CommentAddress<C>: serde::Serialize",
+    deserialize = "CommentAddress<C>: serde::Deserialize<'de>"
+))]
+/// A comment together with its replies, `more_replies` counts the direct replies
+/// that were cut off by the requested depth
+pub struct CommentTreeResponse<C: Context> {
+    pub comment: CommentCollectionResponse<C>,
+    pub replies: Vec<CommentTreeResponse<C>>,
+    pub more_replies: u64,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(bound(
+    serialize = "PostAddress<C>: serde::Serialize",
+    deserialize = "PostAddress<C>: serde::Deserialize<'de>"
+))]
+/// Response for `getCommentsForPost` method
+pub struct PostCommentsResponse<C: Context> {
+    pub post_address: PostAddress<C>,
+    pub comments: Vec<CommentTreeResponse<C>>,
+}
+
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+/// Response for `getOutboxStatus` method, `enabled` is false when the node has no webhook configured
+pub struct OutboxStatusResponse {
+    pub enabled: bool,
+    pub pending: u64,
+    pub oldest_pending_sequence: Option<u64>,
+    pub lag_secs: u64,
+    pub last_delivered_sequence: Option<u64>,
+    pub dead_letters: Vec<DeadLetterResponse>,
+    /// False while the outbox cannot write to disk, changes are held back until it can
+    pub healthy: bool,
+    pub disk_error: Option<String>,
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DeadLetterResponse {
+    pub sequence: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub address: String,
+    /// JSON encoded change envelope that could not be delivered
+    pub changes: String,
 }
 
 
@@ -97,22 +302,68 @@ impl<C: Context> Reddit<C> {
         let c = self
             .user_collections
             .get(&user_address, working_set)
-            .unwrap();
+            .ok_or_else(|| RedditError::UserNotFound { user: user_address.to_string() })?;
 
-        Ok(UserCollectionResponse {
-            username: c.get_username().to_string(),
-            user_address: user_address.clone(),
-        })
+        Ok(UserCollectionResponse::from(&c))
+    }
+
+    #[rpc_method(name = "getUsers")]
+    /// Batch variant of `getUser`, unknown addresses yield `null` at their position
+    pub fn get_users(
+        &self,
+        user_addresses: Vec<UserAddress<C>>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<Vec<Option<UserCollectionResponse<C>>>> {
+        check_batch_size(user_addresses.len())?;
+
+        Ok(user_addresses
+            .iter()
+            .map(|address| {
+                self.user_collections
+                    .get(address, working_set)
+                    .map(|c| UserCollectionResponse::from(&c))
+            })
+            .collect())
+    }
+
+    #[rpc_method(name = "getUserBySender")]
+    /// Get the user owned by a signing address
+    pub fn get_user_by_sender(
+        &self,
+        sender: C::Address,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<UserCollectionResponse<C>> {
+        let user_address = self
+            .user_address_collections
+            .get(&sender, working_set)
+            .ok_or_else(|| RedditError::SenderNotRegistered { sender: sender.to_string() })?;
+
+        self.get_user(user_address, working_set)
+    }
+
+    #[rpc_method(name = "getUserByName")]
+    /// Get the user registered under a username
+    pub fn get_user_by_name(
+        &self,
+        username: &str,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<UserCollectionResponse<C>> {
+        let user_address = normalize_username(username)
+            .ok()
+            .and_then(|canonical| self.username_collections.get(&canonical, working_set))
+            .ok_or_else(|| RedditError::UserNotFound { user: username.to_string() })?;
+
+        self.get_user(user_address, working_set)
     }
     #[rpc_method(name = "getUserAddress")]
     /// Get the collection address
     pub fn get_collection_address(
         &self,
-        user_add: UserAddress<C>,
         username: &str,
         _working_set: &mut WorkingSet<C>,
     ) -> RpcResult<UserAddressResponse<C>> {
-        let ca = get_user_address::<C>(username, user_add.as_ref());
+        let canonical = normalize_username(username)?;
+        let ca = get_user_address::<C>(&canonical);
         Ok(UserAddressResponse {
             user_address: ca,
         })
@@ -131,22 +382,104 @@ impl<C: Context> Reddit<C> {
         let c = self
             .sub_collections
             .get(&sub_address, working_set)
-            .unwrap();
+            .ok_or_else(|| RedditError::SubredditNotFound { sub: sub_address.to_string() })?;
+
+        Ok(self.sub_response(&c, working_set))
+    }
+
+    #[rpc_method(name = "getSubreddits")]
+    /// Batch variant of `getSubreddit`, unknown addresses yield `null` at their position
+    pub fn get_sub_reddits(
+        &self,
+        sub_addresses: Vec<SubAddress<C>>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<Vec<Option<SubRedditCollectionResponse<C>>>> {
+        check_batch_size(sub_addresses.len())?;
+
+        Ok(sub_addresses
+            .iter()
+            .map(|address| {
+                self.sub_collections
+                    .get(address, working_set)
+                    .map(|c| self.sub_response(&c, working_set))
+            })
+            .collect())
+    }
+    #[rpc_method(name = "getSubscriptions")]
+    pub fn get_subscriptions(
+        &self,
+        user_address: UserAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<SubscriptionsResponse<C>> {
+        let subscriptions = self
+            .subscription_collections
+            .get(&user_address, working_set)
+            .unwrap_or_default();
+
+        Ok(SubscriptionsResponse {
+            user_address,
+            subscriptions,
+        })
+    }
+    #[rpc_method(name = "getModerators")]
+    pub fn get_moderators(
+        &self,
+        sub_address: SubAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<ModeratorsResponse<C>> {
+        let c = self
+            .sub_collections
+            .get(&sub_address, working_set)
+            .ok_or_else(|| RedditError::SubredditNotFound { sub: sub_address.to_string() })?;
 
-        Ok(SubRedditCollectionResponse { 
-            subname: c.get_sub_name().to_string(), 
-            desription: c.get_sub_description().to_string(), 
-            subaddress: c.get_sub_address().clone(), 
-            mods: c.get_mods().clone() 
+        Ok(ModeratorsResponse {
+            sub_address,
+            owner: c.get_owner().clone(),
+            mods: c.get_mods().clone(),
         })
     }
+    #[rpc_method(name = "getBans")]
+    pub fn get_bans(
+        &self,
+        sub_address: SubAddress<C>,
+        include_expired: Option<bool>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<BansResponse<C>> {
+        let slot_height = self.current_slot_height.get(working_set).unwrap_or_default();
+        let include_expired = include_expired.unwrap_or(false);
+
+        let bans = self
+            .sub_ban_list
+            .get(&sub_address, working_set)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|user| self.ban_collections.get(&(sub_address.clone(), user), working_set))
+            .filter(|ban| include_expired || ban.is_active(slot_height))
+            .collect();
+
+        Ok(BansResponse { sub_address, bans })
+    }
+    #[rpc_method(name = "getPendingAppeals")]
+    pub fn get_pending_appeals(
+        &self,
+        sub_address: SubAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<AppealsResponse<C>> {
+        let appeals = self
+            .appeal_queue
+            .get(&sub_address, working_set)
+            .unwrap_or_default();
+
+        Ok(AppealsResponse { sub_address, appeals })
+    }
     #[rpc_method(name = "getSubAddress")]
     pub fn get_sub_address(
         &self,
         suname: &str,
         _working_set: &mut WorkingSet<C>,
     ) -> RpcResult<SubAddressResponse<C>> {
-        let ca = get_sub_address::<C>(suname);
+        let canonical = normalize_subname(suname)?;
+        let ca = get_sub_address::<C>(&canonical);
         Ok(SubAddressResponse {
             sub_address: ca,
         })
@@ -159,22 +492,522 @@ impl<C: Context> Reddit<C> {
     pub fn get_post(
         &self,
         post_address: PostAddress<C>,
+        voter: Option<UserAddress<C>>,
         working_set: &mut WorkingSet<C>,
     ) -> RpcResult<PostCollectionResponse<C>> {
         let c = self
             .post_collections
             .get(&post_address, working_set)
-            .unwrap();
-
-        Ok(PostCollectionResponse { 
-            user_address: c.get_user_address().clone(), 
-            sub_address: c.get_sub_address().clone(), 
-            post_address: c.get_post_address().clone(), 
-            post_title: c.get_post_title().to_string(), 
-            content: c.get_post_content().to_string(), 
-            flair: c.get_post_flair().to_string(), 
-            status: c.get_post_status().to_string() 
+            .ok_or_else(|| RedditError::PostNotFound { post: post_address.to_string() })?;
+
+        Ok(self.post_response(&c, voter.as_ref(), working_set))
+    }
+
+    #[rpc_method(name = "getPosts")]
+    /// Batch variant of `getPost`, unknown addresses yield `null` at their position
+    pub fn get_posts(
+        &self,
+        post_addresses: Vec<PostAddress<C>>,
+        voter: Option<UserAddress<C>>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<Vec<Option<PostCollectionResponse<C>>>> {
+        check_batch_size(post_addresses.len())?;
+
+        Ok(post_addresses
+            .iter()
+            .map(|address| {
+                self.post_collections
+                    .get(address, working_set)
+                    .map(|c| self.post_response(&c, voter.as_ref(), working_set))
+            })
+            .collect())
+    }
+
+
+
+
+    #[rpc_method(name = "listSubredditPosts")]
+    /// Lists the posts of a subreddit newest first, deleted posts are skipped
+    /// `limit` defaults to [`DEFAULT_PAGE_SIZE`] and is at most [`MAX_PAGE_SIZE`]
+    pub fn list_subreddit_posts(
+        &self,
+        sub_address: SubAddress<C>,
+        cursor: Option<u64>,
+        limit: Option<usize>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<PostListResponse<C>> {
+        let count = self
+            .sub_post_counts
+            .get(&sub_address, working_set)
+            .unwrap_or_default();
+
+        Ok(self.list_posts(count, cursor, limit, working_set, |position, working_set| {
+            self.sub_post_index.get(&(sub_address.clone(), position), working_set)
+        })?)
+    }
+
+
+    #[rpc_method(name = "listUserPosts")]
+    /// Lists the posts of a user newest first, deleted posts are skipped
+    /// `limit` defaults to [`DEFAULT_PAGE_SIZE`] and is at most [`MAX_PAGE_SIZE`]
+    pub fn list_user_posts(
+        &self,
+        user_address: UserAddress<C>,
+        cursor: Option<u64>,
+        limit: Option<usize>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<PostListResponse<C>> {
+        let count = self
+            .post_nonce_collections
+            .get(&user_address, working_set)
+            .unwrap_or_default();
+
+        Ok(self.list_posts(count, cursor, limit, working_set, |position, working_set| {
+            self.user_post_index.get(&(user_address.clone(), position), working_set)
+        })?)
+    }
+
+
+    #[rpc_method(name = "getFeed")]
+    /// Ranked posts of a subreddit. `window` restricts `TOP` and `CONTROVERSIAL` to recent posts
+    /// and defaults to all time, `cursor` is the `next_cursor` of the previous page.
+    /// `limit` defaults to [`DEFAULT_PAGE_SIZE`] and is at most [`MAX_PAGE_SIZE`].
+    /// Feeds are updated by the finalize hook after each slot, so they can trail `getPost` by one slot
+    pub fn get_feed(
+        &self,
+        sub_address: SubAddress<C>,
+        sort: FeedSort,
+        window: Option<FeedWindow>,
+        cursor: Option<FeedKey<C>>,
+        limit: Option<usize>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<FeedResponse<C>> {
+        let limit = page_size(limit)?;
+        let oldest_slot = match (sort, window.and_then(|window| window.slots())) {
+            (FeedSort::TOP | FeedSort::CONTROVERSIAL, Some(window_slots)) => self
+                .current_slot_height
+                .get(working_set)
+                .unwrap_or_default()
+                .saturating_sub(window_slots),
+            _ => 0,
+        };
+
+        let feed = (sub_address, sort);
+        let mut post_addresses = Vec::new();
+        let mut next_cursor = None;
+        {
+            let mut accessory = working_set.accessory_state();
+            let mut position = self.feed_position_after(&feed, cursor.as_ref(), &mut accessory);
+
+            // Windowed feeds skip older posts, the walk goes on until the page is full so a
+            // page with a cursor is never empty
+            while let Some(current) = position.take() {
+                if post_addresses.len() == limit {
+                    position = Some(current);
+                    break;
+                }
+                let Some(node) = self.feed_node(&feed, &current, &mut accessory) else {
+                    break;
+                };
+
+                if node.created_at_slot >= oldest_slot {
+                    post_addresses.push(current.post_address.clone());
+                }
+                position = node.next.first().cloned().flatten();
+                next_cursor = Some(current);
+            }
+
+            if position.is_none() {
+                next_cursor = None;
+            }
+        }
+
+        let posts = post_addresses
+            .iter()
+            .filter_map(|address| {
+                self.post_collections
+                    .get(address, working_set)
+                    .map(|post| self.post_response(&post, None, working_set))
+            })
+            .collect();
+
+        Ok(FeedResponse { posts, next_cursor })
+    }
+
+
+    #[rpc_method(name = "getNextPostAddress")]
+    /// Predicts the address the next post of `user_address` in `sub_address` will get
+    pub fn get_next_post_address(
+        &self,
+        user_address: UserAddress<C>,
+        sub_address: SubAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<PostAddressResponse<C>> {
+        let nonce = self
+            .post_nonce_collections
+            .get(&user_address, working_set)
+            .unwrap_or_default();
+
+        Ok(PostAddressResponse {
+            post_address: get_post_address::<C>(user_address.as_ref(), sub_address.as_ref(), nonce),
+        })
+    }
+
+
+    #[rpc_method(name = "getOutboxStatus")]
+    /// Delivery state of this node's offchain webhook, not part of the rollup state
+    pub fn get_outbox_status(
+        &self,
+        _working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<OutboxStatusResponse> {
+        let Some(status) = crate::outbox::status() else {
+            return Ok(OutboxStatusResponse {
+                enabled: false,
+                pending: 0,
+                oldest_pending_sequence: None,
+                lag_secs: 0,
+                last_delivered_sequence: None,
+                dead_letters: vec![],
+                healthy: true,
+                disk_error: None,
+            });
+        };
+        let status = status.map_err(|e| jsonrpsee::core::Error::Custom(e.to_string()))?;
+
+        Ok(OutboxStatusResponse {
+            enabled: true,
+            pending: status.pending,
+            oldest_pending_sequence: status.oldest_pending_sequence,
+            lag_secs: status.lag_secs,
+            last_delivered_sequence: status.last_delivered_sequence,
+            healthy: status.disk_error.is_none(),
+            disk_error: status.disk_error,
+            dead_letters: status
+                .dead_letters
+                .into_iter()
+                .map(|entry| DeadLetterResponse {
+                    sequence: entry.sequence,
+                    attempts: entry.attempts,
+                    last_error: entry.last_error,
+                    address: entry.envelope.changes.address.clone(),
+                    changes: serde_json::to_string(&entry.envelope).unwrap_or_default(),
+                })
+                .collect(),
+        })
+    }
+
+
+    #[rpc_method(name = "getComment")]
+    pub fn get_comment(
+        &self,
+        comment_address: CommentAddress<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<CommentCollectionResponse<C>> {
+        let c = self
+            .comment_collections
+            .get(&comment_address, working_set)
+            .ok_or_else(|| RedditError::CommentNotFound { comment: comment_address.to_string() })?;
+
+        Ok(CommentCollectionResponse::from(&c))
+    }
+
+
+    #[rpc_method(name = "getCommentsForPost")]
+    /// Returns the reply tree of a post, `max_depth` defaults to [`MAX_COMMENT_DEPTH`]
+    pub fn get_comments_for_post(
+        &self,
+        post_address: PostAddress<C>,
+        max_depth: Option<u32>,
+        working_set: &mut WorkingSet<C>,
+    ) -> RpcResult<PostCommentsResponse<C>> {
+        let max_depth = max_depth.unwrap_or(MAX_COMMENT_DEPTH).min(MAX_COMMENT_DEPTH);
+
+        let top_level = self
+            .post_comment_collections
+            .get(&post_address, working_set)
+            .unwrap_or_default();
+
+        let comments = top_level
+            .iter()
+            .filter_map(|address| self.build_comment_tree(address, max_depth, working_set))
+            .collect();
+
+        Ok(PostCommentsResponse {
+            post_address,
+            comments,
         })
     }
 
 }
+
+
+fn check_batch_size(len: usize) -> Result<(), RedditError> {
+    if len > MAX_BATCH_SIZE {
+        return Err(RedditError::BatchTooLarge { len, max: MAX_BATCH_SIZE });
+    }
+    Ok(())
+}
+
+/// Page size of a list call, `limit` defaults to [`DEFAULT_PAGE_SIZE`]
+fn page_size(limit: Option<usize>) -> Result<usize, RedditError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(RedditError::InvalidPageSize { limit, max: MAX_PAGE_SIZE });
+    }
+    Ok(limit)
+}
+
+
+impl<C: Context> Reddit<C> {
+    fn sub_response(
+        &self,
+        c: &SubReddit<C>,
+        working_set: &mut WorkingSet<C>,
+    ) -> SubRedditCollectionResponse<C> {
+        SubRedditCollectionResponse {
+            subname: c.get_sub_name().to_string(),
+            desription: c.get_sub_description().to_string(),
+            subaddress: c.get_sub_address().clone(),
+            owner: c.get_owner().clone(),
+            mods: c.get_mod_addresses(),
+            subscriber_count: self
+                .subscriber_counts
+                .get(c.get_sub_address(), working_set)
+                .unwrap_or_default(),
+            archived: c.is_archived(),
+            created_at: c.get_created_at(),
+            last_edited_at: c.get_last_edited_at(),
+        }
+    }
+
+    fn post_response(
+        &self,
+        c: &Post<C>,
+        voter: Option<&UserAddress<C>>,
+        working_set: &mut WorkingSet<C>,
+    ) -> PostCollectionResponse<C> {
+        let my_vote = voter.and_then(|voter| {
+            self.post_vote_collections
+                .get(&(c.get_post_address().clone(), voter.clone()), working_set)
+        });
+
+        PostCollectionResponse {
+            user_address: c.get_user_address().clone(),
+            sub_address: c.get_sub_address().clone(),
+            post_address: c.get_post_address().clone(),
+            post_title: c.get_post_title().to_string(),
+            content: match c.get_post_status() {
+                PostStatus::DELETED => String::new(),
+                _ => c.get_post_content().to_string(),
+            },
+            flair: c.get_post_flair().to_string(),
+            status: c.get_post_status(),
+            score: c.get_score(),
+            upvotes: c.get_upvotes(),
+            downvotes: c.get_downvotes(),
+            created_at: c.get_created_at(),
+            last_edited_at: c.get_last_edited_at(),
+            my_vote,
+        }
+    }
+
+    /// Walks a positional post index downwards from `cursor` (exclusive, defaults to `count`).
+    /// Indexes are append only, so a cursor keeps pointing at the same post across pages.
+    /// At most [`MAX_SCANNED_POSITIONS`] positions are read per call
+    fn list_posts(
+        &self,
+        count: u64,
+        cursor: Option<u64>,
+        limit: Option<usize>,
+        working_set: &mut WorkingSet<C>,
+        post_at: impl Fn(u64, &mut WorkingSet<C>) -> Option<PostAddress<C>>,
+    ) -> Result<PostListResponse<C>, RedditError> {
+        let limit = page_size(limit)?;
+
+        let mut position = cursor.unwrap_or(count).min(count);
+        let scan_end = position.saturating_sub(MAX_SCANNED_POSITIONS);
+        let mut posts = Vec::new();
+
+        while position > scan_end && posts.len() < limit {
+            position -= 1;
+            let post = post_at(position, working_set)
+                .and_then(|address| self.post_collections.get(&address, working_set));
+            if let Some(post) = post {
+                if post.get_post_status() != PostStatus::DELETED {
+                    posts.push(self.post_response(&post, None, working_set));
+                }
+            }
+        }
+
+        Ok(PostListResponse {
+            posts,
+            next_cursor: (position > 0).then_some(position),
+        })
+    }
+
+    fn build_comment_tree(
+        &self,
+        comment_address: &CommentAddress<C>,
+        remaining_depth: u32,
+        working_set: &mut WorkingSet<C>,
+    ) -> Option<CommentTreeResponse<C>> {
+        let comment = self.comment_collections.get(comment_address, working_set)?;
+
+        let (replies, more_replies) = if remaining_depth == 0 {
+            (vec![], comment.get_replies().len() as u64)
+        } else {
+            let replies = comment
+                .get_replies()
+                .iter()
+                .filter_map(|reply| self.build_comment_tree(reply, remaining_depth - 1, working_set))
+                .collect();
+            (replies, 0)
+        };
+
+        Some(CommentTreeResponse {
+            comment: CommentCollectionResponse::from(&comment),
+            replies,
+            more_replies,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{FeedResponse, MAX_PAGE_SIZE};
+    use crate::address::PostAddress;
+    use crate::ranking::{FeedSort, FeedWindow, SLOTS_PER_DAY};
+    use crate::test_utils::{create_post, create_sub, Fixture, C};
+
+    /// Posts `count` times at `slot_height` and runs the finalize hook so the feeds pick them up
+    fn post_at(f: &mut Fixture, slot_height: u64, count: usize) {
+        let owner = f.owner.clone();
+        f.set_slot(slot_height);
+        for _ in 0..count {
+            f.post(&owner);
+        }
+        f.reddit.apply_rank_updates(&mut f.working_set.accessory_state());
+    }
+
+    fn feed(f: &mut Fixture, window: FeedWindow, limit: usize) -> Vec<FeedResponse<C>> {
+        let mut pages = vec![];
+        let mut cursor = None;
+        loop {
+            let page = f
+                .reddit
+                .get_feed(f.sub.clone(), FeedSort::TOP, Some(window), cursor, Some(limit), &mut f.working_set)
+                .unwrap();
+            cursor = page.next_cursor.clone();
+            pages.push(page);
+            if cursor.is_none() {
+                return pages;
+            }
+        }
+    }
+
+    #[test]
+    fn feed_pages_follow_the_limit() {
+        let mut f = Fixture::new();
+        post_at(&mut f, 1, 5);
+
+        let sizes: Vec<_> = feed(&mut f, FeedWindow::ALL, 2).iter().map(|page| page.posts.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+
+        for limit in [0, MAX_PAGE_SIZE + 1] {
+            let page = f.reddit.get_feed(f.sub.clone(), FeedSort::TOP, None, None, Some(limit), &mut f.working_set);
+            assert!(page.is_err());
+        }
+    }
+
+    #[test]
+    fn windowed_feeds_never_return_an_empty_page_with_a_cursor() {
+        let mut f = Fixture::new();
+        post_at(&mut f, 1, 30);
+        post_at(&mut f, 3 * SLOTS_PER_DAY, 3);
+
+        let pages = feed(&mut f, FeedWindow::DAY, 2);
+        let sizes: Vec<_> = pages.iter().map(|page| page.posts.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
+        assert!(pages.iter().flat_map(|page| &page.posts).all(|post| post.created_at.slot_height == 3 * SLOTS_PER_DAY));
+    }
+
+    /// Walks `listSubredditPosts` page by page, returns the addresses of each page and the cursors handed out
+    fn list_pages(f: &mut Fixture, limit: usize) -> (Vec<Vec<PostAddress<C>>>, Vec<Option<u64>>) {
+        let (mut pages, mut cursors) = (vec![], vec![]);
+        let mut cursor = None;
+        loop {
+            let page = f
+                .reddit
+                .list_subreddit_posts(f.sub.clone(), cursor, Some(limit), &mut f.working_set)
+                .unwrap();
+            pages.push(page.posts.into_iter().map(|post| post.post_address).collect());
+            cursors.push(page.next_cursor);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return (pages, cursors);
+            }
+        }
+    }
+
+    #[test]
+    fn list_pages_walk_the_posts_newest_first() {
+        let mut f = Fixture::new();
+        let owner = f.owner.clone();
+        let posts: Vec<_> = (0..5).map(|_| f.post(&owner)).collect();
+
+        let (pages, cursors) = list_pages(&mut f, 2);
+        assert_eq!(
+            pages,
+            vec![
+                vec![posts[4].clone(), posts[3].clone()],
+                vec![posts[2].clone(), posts[1].clone()],
+                vec![posts[0].clone()],
+            ]
+        );
+        assert_eq!(cursors, vec![Some(3), Some(1), None]);
+
+        let all = f.reddit.list_subreddit_posts(f.sub.clone(), None, None, &mut f.working_set).unwrap();
+        assert_eq!(all.posts.len(), 5);
+        assert_eq!(all.next_cursor, None);
+    }
+
+    #[test]
+    fn list_cursors_survive_new_and_deleted_posts() {
+        let mut f = Fixture::new();
+        let owner = f.owner.clone();
+        let posts: Vec<_> = (0..5).map(|_| f.post(&owner)).collect();
+
+        let first = f.reddit.list_subreddit_posts(f.sub.clone(), None, Some(2), &mut f.working_set).unwrap();
+        assert_eq!(first.next_cursor, Some(3));
+
+        // A new post lands above the cursor, a deleted one below it is skipped
+        f.post(&owner);
+        f.reddit.delete_post(posts[2].clone(), &owner.0, &mut f.working_set).unwrap();
+
+        let second = f.reddit.list_subreddit_posts(f.sub.clone(), first.next_cursor, Some(2), &mut f.working_set).unwrap();
+        let addresses: Vec<_> = second.posts.into_iter().map(|post| post.post_address).collect();
+        assert_eq!(addresses, vec![posts[1].clone(), posts[0].clone()]);
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[test]
+    fn user_posts_span_every_subreddit() {
+        let mut f = Fixture::new();
+        let owner = f.owner.clone();
+        let other = create_sub(&f.reddit, &owner, "golang", &mut f.working_set);
+        let first = f.post(&owner);
+        let second = create_post(&f.reddit, &owner, &other, &mut f.working_set);
+
+        let page = f.reddit.list_user_posts(owner.1.clone(), None, Some(1), &mut f.working_set).unwrap();
+        assert_eq!(page.posts[0].post_address, second);
+        let page = f.reddit.list_user_posts(owner.1.clone(), page.next_cursor, Some(1), &mut f.working_set).unwrap();
+        assert_eq!(page.posts[0].post_address, first);
+        assert_eq!(page.next_cursor, None);
+
+        for limit in [0, MAX_PAGE_SIZE + 1] {
+            assert!(f.reddit.list_user_posts(owner.1.clone(), None, Some(limit), &mut f.working_set).is_err());
+        }
+    }
+}
+
@@ -0,0 +1,471 @@
+use sov_modules_api::{AccessoryWorkingSet, Context, StateMapAccessor, StateValueAccessor, WorkingSet};
+
+use crate::{address::{PostAddress, SubAddress}, post::{Post, PostStatus}, Reddit};
+
+
+/// Slots over which the hot score of a post drops by one order of magnitude of votes,
+/// about 12.5 hours with 12 second DA blocks
+pub const HOT_DECAY_SLOTS: u64 = 3_750;
+
+/// Approximate number of DA slots per day, used by the `TOP` time windows
+pub const SLOTS_PER_DAY: u64 = 7_200;
+
+/// Ranking keys are stored as fixed point integers with this many units per 1.0
+const KEY_SCALE: f64 = 10_000_000.0;
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone, Copy)]
+/// Ordering of a subreddit feed
+pub enum FeedSort {
+    HOT,
+    TOP,
+    NEW,
+    CONTROVERSIAL,
+}
+
+impl FeedSort {
+    pub fn all() -> [FeedSort; 4] {
+        [FeedSort::HOT, FeedSort::TOP, FeedSort::NEW, FeedSort::CONTROVERSIAL]
+    }
+
+    /// Ranking key of a post under this ordering, higher keys come first
+    pub fn key<C: Context>(&self, entry: &RankEntry<C>) -> i64 {
+        match self {
+            FeedSort::HOT => hot_key(entry.score, entry.created_at_slot),
+            FeedSort::TOP => entry.score,
+            FeedSort::NEW => entry.created_at_slot as i64,
+            FeedSort::CONTROVERSIAL => controversial_key(entry.upvotes, entry.downvotes),
+        }
+    }
+}
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone, Copy)]
+/// Time window of the `TOP` and `CONTROVERSIAL` feeds, measured in slots back from the current slot
+pub enum FeedWindow {
+    DAY,
+    WEEK,
+    MONTH,
+    YEAR,
+    ALL,
+}
+
+impl FeedWindow {
+    pub fn slots(&self) -> Option<u64> {
+        match self {
+            FeedWindow::DAY => Some(SLOTS_PER_DAY),
+            FeedWindow::WEEK => Some(7 * SLOTS_PER_DAY),
+            FeedWindow::MONTH => Some(30 * SLOTS_PER_DAY),
+            FeedWindow::YEAR => Some(365 * SLOTS_PER_DAY),
+            FeedWindow::ALL => None,
+        }
+    }
+}
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone)]
+/// Snapshot of the ranking inputs of a post, queued by the call handlers
+/// and applied to the feeds by the finalize hook
+pub struct RankEntry<C: Context> {
+    pub post_address: PostAddress<C>,
+    pub sub_address: SubAddress<C>,
+    pub score: i64,
+    pub upvotes: u64,
+    pub downvotes: u64,
+    pub created_at_slot: u64,
+    pub removed: bool,
+}
+
+impl<C: Context> From<&Post<C>> for RankEntry<C> {
+    fn from(post: &Post<C>) -> Self {
+        RankEntry {
+            post_address: post.get_post_address().clone(),
+            sub_address: post.get_sub_address().clone(),
+            score: post.get_score(),
+            upvotes: post.get_upvotes(),
+            downvotes: post.get_downvotes(),
+            created_at_slot: post.get_created_at_slot(),
+            removed: post.get_post_status() == PostStatus::DELETED,
+        }
+    }
+}
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone)]
+/// Position of a post in a feed. Feeds are ordered by descending `key`, posts with the same key by address
+pub struct FeedKey<C: Context> {
+    pub key: i64,
+    pub post_address: PostAddress<C>,
+}
+
+impl<C: Context> FeedKey<C> {
+    /// Whether `self` comes before `other` in the feed
+    pub fn precedes(&self, other: &FeedKey<C>) -> bool {
+        self.key > other.key
+            || (self.key == other.key && self.post_address.as_ref() < other.post_address.as_ref())
+    }
+}
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone)]
+/// Entry of a feed skip list. Level 0 links every post of the feed in order,
+/// each higher level skips about three quarters of the level below
+pub struct FeedNode<C: Context> {
+    pub created_at_slot: u64,
+    /// Following entry on each level this node is part of
+    pub next: Vec<Option<FeedKey<C>>>,
+}
+
+
+/// Reddit's hot formula with the post age measured in slots instead of seconds
+fn hot_key(score: i64, created_at_slot: u64) -> i64 {
+    let order = (score.unsigned_abs().max(1) as f64).log10();
+    let sign = score.signum() as f64;
+    let hot = sign * order + created_at_slot as f64 / HOT_DECAY_SLOTS as f64;
+    (hot * KEY_SCALE) as i64
+}
+
+/// Posts with many votes split evenly between up and down rank highest
+fn controversial_key(upvotes: u64, downvotes: u64) -> i64 {
+    if upvotes == 0 || downvotes == 0 {
+        return 0;
+    }
+    let magnitude = (upvotes + downvotes) as f64;
+    let balance = upvotes.min(downvotes) as f64 / upvotes.max(downvotes) as f64;
+    (magnitude.powf(balance) * KEY_SCALE) as i64
+}
+
+
+/// Number of levels of the feed skip lists, enough for millions of posts per feed
+pub const MAX_FEED_LEVEL: usize = 12;
+
+/// Number of skip list levels `post_address` is part of. Derived from the address, which is a hash,
+/// so every node takes the same levels in every feed and on every node
+fn feed_height<C: Context>(post_address: &PostAddress<C>) -> usize {
+    let mut height = 1;
+    for byte in post_address.as_ref() {
+        for pair in 0..4 {
+            if height == MAX_FEED_LEVEL || (byte >> (pair * 2)) & 0b11 != 0 {
+                return height;
+            }
+            height += 1;
+        }
+    }
+    height
+}
+
+
+type FeedId<C> = (SubAddress<C>, FeedSort);
+
+
+impl<C: Context> Reddit<C> {
+    /// Queues the current ranking inputs of `post`, the feeds pick them up in the finalize hook
+    pub(crate) fn queue_rank_update(&self, post: &Post<C>, working_set: &mut WorkingSet<C>) {
+        let mut accessory = working_set.accessory_state();
+        let mut pending = self.pending_rank_updates.get(&mut accessory).unwrap_or_default();
+        pending.retain(|entry| entry.post_address != *post.get_post_address());
+        pending.push(RankEntry::from(post));
+        self.pending_rank_updates.set(&pending, &mut accessory);
+    }
+
+    /// Moves every queued post to its new position in each feed of its subreddit
+    pub(crate) fn apply_rank_updates(&self, accessory_working_set: &mut AccessoryWorkingSet<C>) {
+        let Some(pending) = self.pending_rank_updates.get(accessory_working_set) else {
+            return;
+        };
+
+        for entry in pending {
+            for sort in FeedSort::all() {
+                let feed = (entry.sub_address.clone(), sort);
+                let rank_key = (entry.post_address.clone(), sort);
+                let key = sort.key(&entry);
+                let previous = self.feed_ranks.get(&rank_key, accessory_working_set);
+
+                if previous == Some(key) && !entry.removed {
+                    continue;
+                }
+                if let Some(previous) = previous {
+                    let position = FeedKey { key: previous, post_address: entry.post_address.clone() };
+                    self.unlink_feed_node(&feed, &position, accessory_working_set);
+                }
+
+                if entry.removed {
+                    self.feed_ranks.delete(&rank_key, accessory_working_set);
+                } else {
+                    let position = FeedKey { key, post_address: entry.post_address.clone() };
+                    self.link_feed_node(&feed, position, entry.created_at_slot, accessory_working_set);
+                    self.feed_ranks.set(&rank_key, &key, accessory_working_set);
+                }
+            }
+        }
+
+        self.pending_rank_updates.delete(accessory_working_set);
+    }
+
+    /// First position of the feed after `cursor`, or its first position without a cursor.
+    /// The cursor does not have to be in the feed anymore
+    pub(crate) fn feed_position_after(
+        &self,
+        feed: &FeedId<C>,
+        cursor: Option<&FeedKey<C>>,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) -> Option<FeedKey<C>> {
+        let Some(cursor) = cursor else {
+            return self.feed_next(feed, None, 0, accessory_working_set);
+        };
+
+        let predecessors = self.feed_predecessors(feed, cursor, accessory_working_set);
+        let next = self.feed_next(feed, predecessors[0].as_ref(), 0, accessory_working_set);
+        match next {
+            Some(next) if &next == cursor => self.feed_next(feed, Some(&next), 0, accessory_working_set),
+            next => next,
+        }
+    }
+
+    /// Last position before `target` on every level, `None` is the head of the feed
+    fn feed_predecessors(
+        &self,
+        feed: &FeedId<C>,
+        target: &FeedKey<C>,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) -> Vec<Option<FeedKey<C>>> {
+        let mut predecessors = vec![None; MAX_FEED_LEVEL];
+        let mut current: Option<FeedKey<C>> = None;
+        let mut current_next = self.feed_links(feed, None, accessory_working_set);
+
+        for level in (0..MAX_FEED_LEVEL).rev() {
+            while let Some(next) = current_next.get(level).cloned().flatten() {
+                if !next.precedes(target) {
+                    break;
+                }
+                current_next = self.feed_links(feed, Some(&next), accessory_working_set);
+                current = Some(next);
+            }
+            predecessors[level] = current.clone();
+        }
+
+        predecessors
+    }
+
+    fn link_feed_node(
+        &self,
+        feed: &FeedId<C>,
+        position: FeedKey<C>,
+        created_at_slot: u64,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) {
+        let predecessors = self.feed_predecessors(feed, &position, accessory_working_set);
+        let height = feed_height(&position.post_address);
+
+        let mut next = Vec::with_capacity(height);
+        for (level, predecessor) in predecessors.iter().enumerate().take(height) {
+            next.push(self.feed_next(feed, predecessor.as_ref(), level, accessory_working_set));
+            self.set_feed_next(feed, predecessor.as_ref(), level, Some(position.clone()), accessory_working_set);
+        }
+
+        let node = FeedNode { created_at_slot, next };
+        self.feed_entries.set(&(feed.0.clone(), feed.1, position), &node, accessory_working_set);
+    }
+
+    fn unlink_feed_node(
+        &self,
+        feed: &FeedId<C>,
+        position: &FeedKey<C>,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) {
+        let entry_key = (feed.0.clone(), feed.1, position.clone());
+        let Some(node) = self.feed_entries.get(&entry_key, accessory_working_set) else {
+            return;
+        };
+
+        let predecessors = self.feed_predecessors(feed, position, accessory_working_set);
+        for (level, next) in node.next.into_iter().enumerate() {
+            self.set_feed_next(feed, predecessors[level].as_ref(), level, next, accessory_working_set);
+        }
+
+        self.feed_entries.delete(&entry_key, accessory_working_set);
+    }
+
+    /// Outgoing links of `position`, or of the head of the feed for `None`
+    fn feed_links(
+        &self,
+        feed: &FeedId<C>,
+        position: Option<&FeedKey<C>>,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) -> Vec<Option<FeedKey<C>>> {
+        match position {
+            None => self
+                .feed_heads
+                .get(feed, accessory_working_set)
+                .unwrap_or_else(|| vec![None; MAX_FEED_LEVEL]),
+            Some(position) => self
+                .feed_entries
+                .get(&(feed.0.clone(), feed.1, position.clone()), accessory_working_set)
+                .map(|node| node.next)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn feed_node(
+        &self,
+        feed: &FeedId<C>,
+        position: &FeedKey<C>,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) -> Option<FeedNode<C>> {
+        self.feed_entries
+            .get(&(feed.0.clone(), feed.1, position.clone()), accessory_working_set)
+    }
+
+    fn feed_next(
+        &self,
+        feed: &FeedId<C>,
+        position: Option<&FeedKey<C>>,
+        level: usize,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) -> Option<FeedKey<C>> {
+        self.feed_links(feed, position, accessory_working_set)
+            .get(level)
+            .cloned()
+            .flatten()
+    }
+
+    fn set_feed_next(
+        &self,
+        feed: &FeedId<C>,
+        position: Option<&FeedKey<C>>,
+        level: usize,
+        next: Option<FeedKey<C>>,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
+    ) {
+        match position {
+            None => {
+                let mut heads = self.feed_links(feed, None, accessory_working_set);
+                heads[level] = next;
+                self.feed_heads.set(feed, &heads, accessory_working_set);
+            }
+            Some(position) => {
+                let entry_key = (feed.0.clone(), feed.1, position.clone());
+                if let Some(mut node) = self.feed_entries.get(&entry_key, accessory_working_set) {
+                    node.next[level] = next;
+                    self.feed_entries.set(&entry_key, &node, accessory_working_set);
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::{StateValueAccessor, WorkingSet};
+
+    use super::{FeedSort, RankEntry};
+    use crate::address::{PostAddress, SubAddress};
+    use crate::test_utils::{new_working_set, C};
+    use crate::utils::{get_post_address, get_sub_address};
+    use crate::Reddit;
+
+    fn entry(sub: &SubAddress<C>, nonce: u64, score: i64, removed: bool) -> RankEntry<C> {
+        RankEntry {
+            post_address: get_post_address::<C>(b"author", sub.as_ref(), nonce),
+            sub_address: sub.clone(),
+            score,
+            upvotes: score.max(0) as u64,
+            downvotes: (-score).max(0) as u64,
+            created_at_slot: nonce,
+            removed,
+        }
+    }
+
+    fn apply(reddit: &Reddit<C>, entries: Vec<RankEntry<C>>, working_set: &mut WorkingSet<C>) {
+        let mut accessory = working_set.accessory_state();
+        reddit.pending_rank_updates.set(&entries, &mut accessory);
+        reddit.apply_rank_updates(&mut accessory);
+    }
+
+    fn walk(reddit: &Reddit<C>, sub: &SubAddress<C>, sort: FeedSort, working_set: &mut WorkingSet<C>) -> Vec<PostAddress<C>> {
+        let feed = (sub.clone(), sort);
+        let mut accessory = working_set.accessory_state();
+        let mut posts = vec![];
+        let mut position = reddit.feed_position_after(&feed, None, &mut accessory);
+        while let Some(current) = position {
+            position = reddit.feed_position_after(&feed, Some(&current), &mut accessory);
+            posts.push(current.post_address);
+        }
+        posts
+    }
+
+    /// Addresses of `entries` ordered the way the `TOP` feed should list them
+    fn expected_top(entries: &[RankEntry<C>]) -> Vec<PostAddress<C>> {
+        let mut live: Vec<_> = entries.iter().filter(|entry| !entry.removed).collect();
+        live.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.post_address.as_ref().cmp(b.post_address.as_ref()))
+        });
+        live.into_iter().map(|entry| entry.post_address.clone()).collect()
+    }
+
+    #[test]
+    fn feeds_follow_score_changes_and_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut working_set = new_working_set(&dir);
+        let reddit = Reddit::<C>::default();
+        let sub = get_sub_address::<C>("rust");
+
+        let mut entries: Vec<_> = (0..200).map(|nonce| entry(&sub, nonce, (nonce as i64 * 37) % 23 - 11, false)).collect();
+        apply(&reddit, entries.clone(), &mut working_set);
+        assert_eq!(walk(&reddit, &sub, FeedSort::TOP, &mut working_set), expected_top(&entries));
+
+        let updates: Vec<_> = (0..200)
+            .step_by(7)
+            .map(|nonce| entry(&sub, nonce, (nonce as i64 * 13) % 31 - 15, nonce % 3 == 0))
+            .collect();
+        for update in &updates {
+            entries[update.created_at_slot as usize] = update.clone();
+        }
+        apply(&reddit, updates, &mut working_set);
+        assert_eq!(walk(&reddit, &sub, FeedSort::TOP, &mut working_set), expected_top(&entries));
+
+        let newest_first: Vec<_> = entries.iter().rev().filter(|e| !e.removed).map(|e| e.post_address.clone()).collect();
+        assert_eq!(walk(&reddit, &sub, FeedSort::NEW, &mut working_set), newest_first);
+    }
+
+    #[test]
+    fn feeds_of_other_subreddits_are_independent() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut working_set = new_working_set(&dir);
+        let reddit = Reddit::<C>::default();
+        let (rust, go) = (get_sub_address::<C>("rust"), get_sub_address::<C>("golang"));
+
+        apply(&reddit, vec![entry(&rust, 0, 5, false), entry(&go, 1, 3, false)], &mut working_set);
+
+        assert_eq!(walk(&reddit, &rust, FeedSort::HOT, &mut working_set).len(), 1);
+        assert_eq!(walk(&reddit, &go, FeedSort::HOT, &mut working_set).len(), 1);
+    }
+}
@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use sov_modules_api::{Context, StateMapAccessor, StateValueAccessor, WorkingSet};
+
+use crate::{address::UserAddress, error::RedditError, Reddit};
+
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, borsh::BorshDeserialize, borsh::BorshSerialize)]
+/// At most `max_actions` actions within the last `window_slots` slots
+pub struct Quota {
+    pub max_actions: u32,
+    pub window_slots: u64,
+}
+
+impl Quota {
+    /// Quota of a restricted user: `max_actions` divided by `divisor` but at least one,
+    /// unless the quota does not allow the action at all
+    pub fn restricted(&self, divisor: u32) -> Quota {
+        let max_actions = match self.max_actions {
+            0 => 0,
+            max_actions => (max_actions / divisor.max(1)).max(1),
+        };
+        Quota { max_actions, ..*self }
+    }
+
+    /// Drops the actions of `recent` that are no longer inside the window ending at `slot_height`
+    pub fn trim_window(&self, recent: &mut Vec<u64>, slot_height: u64) {
+        let window_start = slot_height.saturating_sub(self.window_slots);
+        recent.retain(|slot| *slot > window_start);
+    }
+}
+
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, borsh::BorshDeserialize, borsh::BorshSerialize)]
+/// Per-user quotas, an unset quota leaves the action unlimited
+pub struct RateLimits {
+    #[serde(default)]
+    pub posts: Option<Quota>,
+    #[serde(default)]
+    pub comments: Option<Quota>,
+    #[serde(default)]
+    pub subreddits: Option<Quota>,
+    /// Users created less than this many slots ago are restricted
+    #[serde(default)]
+    pub new_account_slots: u64,
+    /// Users with less karma than this are restricted
+    #[serde(default)]
+    pub min_karma: i64,
+    /// Restricted users get every quota divided by this factor, but keep one action per window
+    /// of every quota that allows any
+    #[serde(default)]
+    pub restricted_divisor: u32,
+}
+
+
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone, Copy)]
+/// Actions that count against a quota
+pub enum RateLimitedAction {
+    CREATE_POST,
+    CREATE_COMMENT,
+    CREATE_SUBREDDIT,
+}
+
+impl RateLimitedAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitedAction::CREATE_POST => "CREATE_POST",
+            RateLimitedAction::CREATE_COMMENT => "CREATE_COMMENT",
+            RateLimitedAction::CREATE_SUBREDDIT => "CREATE_SUBREDDIT",
+        }
+    }
+
+    fn quota(&self, limits: &RateLimits) -> Option<Quota> {
+        match self {
+            RateLimitedAction::CREATE_POST => limits.posts,
+            RateLimitedAction::CREATE_COMMENT => limits.comments,
+            RateLimitedAction::CREATE_SUBREDDIT => limits.subreddits,
+        }
+    }
+}
+
+
+impl<C: Context> Reddit<C> {
+    /// Records `action` against the user's quota, failing without recording when the quota is used up.
+    /// Called by the handlers once the call is valid, the record is reverted with the rest of the call
+    pub(crate) fn check_rate_limit(
+        &self,
+        user_address: &UserAddress<C>,
+        action: RateLimitedAction,
+        working_set: &mut WorkingSet<C>,
+    ) -> Result<(), RedditError> {
+        let Some(limits) = self.rate_limits.get(working_set) else {
+            return Ok(());
+        };
+        let Some(mut quota) = action.quota(&limits) else {
+            return Ok(());
+        };
+
+        let now = self.now(working_set);
+
+        if self.is_restricted(user_address, &limits, now.slot_height, working_set) {
+            quota = quota.restricted(limits.restricted_divisor);
+        }
+
+        let key = (user_address.clone(), action);
+        let mut recent = self.rate_limit_log.get(&key, working_set).unwrap_or_default();
+        quota.trim_window(&mut recent, now.slot_height);
+
+        if recent.len() >= quota.max_actions as usize {
+            return Err(RedditError::RateLimited {
+                action: action.as_str().to_string(),
+                max_actions: quota.max_actions,
+                window_slots: quota.window_slots,
+            });
+        }
+
+        recent.push(now.slot_height);
+        self.rate_limit_log.set(&key, &recent, working_set);
+        Ok(())
+    }
+
+    fn is_restricted(
+        &self,
+        user_address: &UserAddress<C>,
+        limits: &RateLimits,
+        slot_height: u64,
+        working_set: &mut WorkingSet<C>,
+    ) -> bool {
+        match self.user_collections.get(user_address, working_set) {
+            Some(user) => {
+                let age = slot_height.saturating_sub(user.get_created_at().slot_height);
+                age < limits.new_account_slots || user.get_karma() < limits.min_karma
+            }
+            None => true,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::StateValueAccessor;
+
+    use super::{Quota, RateLimits};
+    use crate::address::SubAddress;
+    use crate::error::RedditError;
+    use crate::test_utils::{Fixture, C};
+    use crate::utils::get_sub_address;
+
+    fn quota(max_actions: u32, window_slots: u64) -> Option<Quota> {
+        Some(Quota { max_actions, window_slots })
+    }
+
+    /// The limits are set after the owner created the fixture's subreddit, so that one is never counted
+    fn setup(limits: RateLimits) -> Fixture {
+        let mut f = Fixture::new();
+        f.reddit.rate_limits.set(&limits, &mut f.working_set);
+        f
+    }
+
+    /// Post by the owner at `slot_height`
+    fn post(f: &mut Fixture, sub: &SubAddress<C>, slot_height: u64) -> Result<(), RedditError> {
+        f.set_slot(slot_height);
+        f.reddit
+            .create_new_post("title", "flair", "content", sub.clone(), &f.owner.0, &mut f.working_set)
+            .map(|_| ())
+            .map_err(|e| e.downcast::<RedditError>().unwrap())
+    }
+
+    #[test]
+    fn restricted_quota_keeps_one_action_unless_none_are_allowed() {
+        assert_eq!(quota(10, 5).unwrap().restricted(4).max_actions, 2);
+        assert_eq!(quota(3, 5).unwrap().restricted(4).max_actions, 1);
+        assert_eq!(quota(0, 5).unwrap().restricted(4).max_actions, 0);
+        assert_eq!(quota(3, 5).unwrap().restricted(0).max_actions, 3);
+    }
+
+    #[test]
+    fn window_keeps_only_actions_after_its_start() {
+        let mut recent = vec![1, 5, 10, 11];
+        quota(1, 10).unwrap().trim_window(&mut recent, 15);
+        assert_eq!(recent, vec![10, 11]);
+
+        let mut early = vec![0, 3];
+        quota(1, 10).unwrap().trim_window(&mut early, 4);
+        assert_eq!(early, vec![3]);
+    }
+
+    #[test]
+    fn actions_are_allowed_again_once_they_leave_the_window() {
+        let limits = RateLimits { posts: quota(2, 10), ..Default::default() };
+        let mut f = setup(limits);
+        let sub = f.sub.clone();
+
+        assert!(post(&mut f, &sub, 1).is_ok());
+        assert!(post(&mut f, &sub, 2).is_ok());
+        assert_eq!(
+            post(&mut f, &sub, 3),
+            Err(RedditError::RateLimited { action: "CREATE_POST".to_string(), max_actions: 2, window_slots: 10 })
+        );
+
+        assert!(post(&mut f, &sub, 11).is_ok());
+        assert!(post(&mut f, &sub, 11).is_err());
+        assert!(post(&mut f, &sub, 12).is_ok());
+    }
+
+    #[test]
+    fn new_accounts_get_the_restricted_quota() {
+        let limits = RateLimits {
+            posts: quota(3, 1_000),
+            new_account_slots: 100,
+            restricted_divisor: 2,
+            ..Default::default()
+        };
+        let mut f = setup(limits);
+        let sub = f.sub.clone();
+
+        assert!(post(&mut f, &sub, 10).is_ok());
+        assert!(post(&mut f, &sub, 20).is_err());
+
+        // Past the new account period the full quota applies, the earlier post still counts
+        assert!(post(&mut f, &sub, 150).is_ok());
+        assert!(post(&mut f, &sub, 151).is_ok());
+        assert!(post(&mut f, &sub, 152).is_err());
+    }
+
+    #[test]
+    fn a_zero_quota_blocks_restricted_users_too() {
+        let limits = RateLimits {
+            posts: quota(0, 10),
+            min_karma: 1,
+            restricted_divisor: 2,
+            ..Default::default()
+        };
+        let mut f = setup(limits);
+        let sub = f.sub.clone();
+
+        assert!(post(&mut f, &sub, 1).is_err());
+    }
+
+    #[test]
+    fn invalid_calls_do_not_use_up_the_quota() {
+        let limits = RateLimits { posts: quota(1, 10), ..Default::default() };
+        let mut f = setup(limits);
+        let sub = f.sub.clone();
+
+        let missing = get_sub_address::<C>("missing");
+        assert!(matches!(post(&mut f, &missing, 1), Err(RedditError::SubredditNotFound { .. })));
+
+        assert!(post(&mut f, &sub, 1).is_ok());
+        assert!(matches!(post(&mut f, &sub, 1), Err(RedditError::RateLimited { .. })));
+    }
+
+    #[test]
+    fn every_action_has_its_own_quota() {
+        let limits = RateLimits {
+            posts: quota(1, 10),
+            comments: quota(1, 10),
+            subreddits: quota(0, 10),
+            ..Default::default()
+        };
+        let mut f = setup(limits);
+        f.set_slot(1);
+
+        let owner = f.owner.clone();
+        let post_address = f.post(&owner);
+        f.reddit.create_new_comment(post_address.clone(), None, "first", &owner.0, &mut f.working_set).unwrap();
+        let error = f.reddit.create_new_comment(post_address, None, "second", &owner.0, &mut f.working_set).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RedditError>(),
+            Some(&RedditError::RateLimited { action: "CREATE_COMMENT".to_string(), max_actions: 1, window_slots: 10 })
+        );
+
+        let error = f
+            .reddit
+            .create_new_subreddit(owner.1.clone(), "another", "description", &owner.0, &mut f.working_set)
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref::<RedditError>(), Some(RedditError::RateLimited { .. })));
+    }
+}
@@ -0,0 +1,110 @@
+//! Signing of offchain webhook payloads. Receivers can depend on this module to check that a
+//! request really came from a node holding the shared secret
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+
+/// Header carrying `sha256=<hex hmac of the raw body>`
+pub const SIGNATURE_HEADER: &str = "X-Reddit-Signature";
+
+/// Header carrying the [`crate::offchain::SCHEMA_VERSION`] of the body
+pub const SCHEMA_VERSION_HEADER: &str = "X-Reddit-Schema-Version";
+
+type HmacSha256 = Hmac<Sha256>;
+
+
+/// HMAC-SHA256 of `body` keyed with `secret` (RFC 2104)
+pub fn hmac_sha256(secret: &[u8], body: &[u8]) -> [u8; 32] {
+    keyed(secret, body).finalize().into_bytes().into()
+}
+
+/// Value of the [`SIGNATURE_HEADER`] for `body`
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    format!("sha256={}", hex::encode(hmac_sha256(secret, body)))
+}
+
+/// Checks a [`SIGNATURE_HEADER`] value against the raw request body in constant time
+pub fn verify(secret: &[u8], body: &[u8], header: &str) -> bool {
+    let Some(signature) = header.strip_prefix("sha256=").and_then(|hex| hex::decode(hex).ok()) else {
+        return false;
+    };
+    keyed(secret, body).verify_slice(&signature).is_ok()
+}
+
+fn keyed(secret: &[u8], body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// HMAC-SHA-256 test cases 1-4, 6 and 7 of RFC 4231, case 5 only checks a truncated output
+    fn rfc4231_cases() -> Vec<(Vec<u8>, Vec<u8>, &'static str)> {
+        vec![
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                (0x01..=0x19).collect(),
+                vec![0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size data. \
+                  The key needs to be hashed before being used by the HMAC algorithm."
+                    .to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ]
+    }
+
+    #[test]
+    fn signs_rfc4231_vectors() {
+        for (key, data, expected) in rfc4231_cases() {
+            assert_eq!(hex::encode(hmac_sha256(&key, &data)), expected);
+            assert_eq!(sign(&key, &data), format!("sha256={}", expected));
+        }
+    }
+
+    #[test]
+    fn verifies_rfc4231_vectors() {
+        for (key, data, expected) in rfc4231_cases() {
+            assert!(verify(&key, &data, &format!("sha256={}", expected)));
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_signatures() {
+        let (key, data, expected) = rfc4231_cases().remove(1);
+        let header = format!("sha256={}", expected);
+
+        assert!(!verify(&key, b"what do ya want for something?", &header));
+        assert!(!verify(b"Jeff", &data, &header));
+        assert!(!verify(&key, &data, &header[..header.len() - 2]));
+        assert!(!verify(&key, &data, expected));
+        assert!(!verify(&key, &data, "sha256=not hex"));
+    }
+}
@@ -1,8 +1,7 @@
 use std::collections::HashMap;
-use anyhow::{anyhow, bail};
 use sov_modules_api::{Context, StateMap, StateMapAccessor, WorkingSet};
 
-use crate::{address::{SubAddress, UserAddress}, utils::get_sub_address};
+use crate::{address::{SubAddress, UserAddress}, error::RedditError, moderator::{ModPermission, Moderator}, timestamp::SlotTime, utils::get_sub_address};
 
 
 
@@ -18,7 +17,14 @@ pub struct SubReddit<C: Context> {
     subaddress: SubAddress<C>,
     subname: String,
     description: String,
-    mods: Vec<UserAddress<C>>
+    owner: UserAddress<C>,
+    /// Ordered by seniority, the owner is always the first moderator
+    mods: Vec<Moderator<C>>,
+    archived: bool,
+    archive_after_slots: Option<u64>,
+    created_at: SlotTime,
+    /// Last change of the subreddit settings
+    last_edited_at: Option<SlotTime>,
 }
 
 impl<C: Context> SubReddit<C> {
@@ -26,28 +32,33 @@ impl<C: Context> SubReddit<C> {
 
  pub fn new(
     subname: &str,
+    canonical_subname: &str,
     description: &str,
     sub_collections: &StateMap<SubAddress<C> , SubReddit<C>>,
-    context: &C,
+    creator: UserAddress<C>,
+    now: SlotTime,
     working_set: &mut WorkingSet<C>
  ) -> anyhow::Result<(SubAddress<C> , SubReddit<C>)> {
 
-         let creator = context.sender();
-
-    let sub_address = get_sub_address(subname);
+    let sub_address = get_sub_address(canonical_subname);
 
 
     let sub_add = sub_collections.get(&sub_address, working_set);
 
     if sub_add.is_some() {
-        Err(anyhow!( "Subreddit with subname={} already exists", subname ))
+        Err(RedditError::SubredditAlreadyExists { subname: subname.to_string() }.into())
     } else {
         Ok(  
             (sub_address.clone() , SubReddit {
              subaddress: sub_address.clone(), 
             subname: subname.to_string(), 
             description: description.to_string(), 
-            mods: vec![UserAddress::new(creator)] })
+            owner: creator.clone(),
+            mods: vec![Moderator::new(creator, &ModPermission::all())],
+            archived: false,
+            archive_after_slots: None,
+            created_at: now,
+            last_edited_at: None })
 
          )
     }
@@ -75,9 +86,199 @@ impl<C: Context> SubReddit<C> {
 
 
            #[allow(dead_code)]
-    pub fn get_mods(&self) -> &Vec<UserAddress<C>> {
+    pub fn get_mods(&self) -> &Vec<Moderator<C>> {
         &self.mods
     }
 
 
-}
\ No newline at end of file
+    /// Moderator addresses ordered by seniority
+    pub fn get_mod_addresses(&self) -> Vec<UserAddress<C>> {
+        self.mods.iter().map(|m| m.get_user_address().clone()).collect()
+    }
+
+
+    pub fn get_moderator(&self, user: &UserAddress<C>) -> Option<&Moderator<C>> {
+        self.mods.iter().find(|m| m.get_user_address() == user)
+    }
+
+
+    #[allow(dead_code)]
+    pub fn get_owner(&self) -> &UserAddress<C> {
+        &self.owner
+    }
+
+
+    pub fn is_moderator(&self, user: &UserAddress<C>) -> bool {
+        self.get_moderator(user).is_some()
+    }
+
+
+    /// The owner implicitly holds every permission
+    pub fn has_permission(&self, user: &UserAddress<C>, permission: ModPermission) -> bool {
+        &self.owner == user
+            || self
+                .get_moderator(user)
+                .map(|m| m.has_permission(permission))
+                .unwrap_or(false)
+    }
+
+
+    /// Seniority of a moderator, 0 is the owner and higher numbers are more junior
+    pub fn moderator_rank(&self, user: &UserAddress<C>) -> Option<usize> {
+        self.mods.iter().position(|m| m.get_user_address() == user)
+    }
+
+
+    /// New moderators are the most junior
+    pub(crate) fn add_moderator(&mut self, user: UserAddress<C>, permissions: &[ModPermission]) {
+        self.mods.push(Moderator::new(user, permissions));
+    }
+
+
+    pub(crate) fn set_moderator_permissions(&mut self, user: &UserAddress<C>, permissions: &[ModPermission]) {
+        if let Some(m) = self.mods.iter_mut().find(|m| m.get_user_address() == user) {
+            m.set_permissions(permissions);
+        }
+    }
+
+
+    pub(crate) fn remove_moderator(&mut self, user: &UserAddress<C>) {
+        self.mods.retain(|m| m.get_user_address() != user);
+    }
+
+
+    /// `new_owner` must already be a moderator, it becomes the most senior one with every permission
+    pub(crate) fn transfer_ownership(&mut self, new_owner: UserAddress<C>) {
+        self.remove_moderator(&new_owner);
+        self.mods.insert(0, Moderator::new(new_owner.clone(), &ModPermission::all()));
+        self.owner = new_owner;
+    }
+
+
+    /// Overrides the global `archive_after_slots` of [`crate::RedditConfig`] for posts in this subreddit
+    #[allow(dead_code)]
+    pub fn get_archive_after_slots(&self) -> Option<u64> {
+        self.archive_after_slots
+    }
+
+
+    pub(crate) fn set_archive_after_slots(&mut self, archive_after_slots: Option<u64>, now: SlotTime) {
+        self.archive_after_slots = archive_after_slots;
+        self.last_edited_at = Some(now);
+    }
+
+
+    /// Archived subreddits no longer accept new posts
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+
+    pub(crate) fn archive(&mut self, now: SlotTime) {
+        self.archived = true;
+        self.last_edited_at = Some(now);
+    }
+
+
+    #[allow(dead_code)]
+    pub fn get_created_at(&self) -> SlotTime {
+        self.created_at
+    }
+
+
+    #[allow(dead_code)]
+    pub fn get_last_edited_at(&self) -> Option<SlotTime> {
+        self.last_edited_at
+    }
+
+
+}
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::StateMapAccessor;
+
+    use crate::moderator::ModPermission;
+    use crate::test_utils::{create_sub, error_code, last_event_key, sender, Fixture, C};
+    use crate::utils::get_sub_address;
+
+    #[test]
+    fn archived_subreddits_reject_new_posts() {
+        let mut f = Fixture::new();
+        let owner = f.owner.clone();
+        let post = f.post(&owner);
+
+        f.reddit.archive_subreddit(f.sub.clone(), &owner.0, &mut f.working_set).unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "SubredditArchived");
+        assert!(f.reddit.sub_collections.get(&f.sub, &mut f.working_set).unwrap().is_archived());
+
+        let posted = f.reddit.create_new_post("title", "flair", "content", f.sub.clone(), &owner.0, &mut f.working_set);
+        assert_eq!(error_code(posted), "SUBREDDIT_ARCHIVED");
+        // Existing posts are left alone
+        assert!(f.reddit.post_collections.get(&post, &mut f.working_set).is_some());
+
+        let again = f.reddit.archive_subreddit(f.sub.clone(), &owner.0, &mut f.working_set);
+        assert_eq!(error_code(again), "SUBREDDIT_ARCHIVED");
+    }
+
+    #[test]
+    fn only_the_owner_archives_a_subreddit() {
+        let mut f = Fixture::new();
+        let (owner, moderator) = (f.owner.clone(), f.user("moderator"));
+        f.reddit
+            .add_moderator(f.sub.clone(), moderator.1.clone(), ModPermission::all(), &owner.0, &mut f.working_set)
+            .unwrap();
+
+        let archived = f.reddit.archive_subreddit(f.sub.clone(), &moderator.0, &mut f.working_set);
+        assert_eq!(error_code(archived), "NOT_SUBREDDIT_OWNER");
+        assert!(!f.reddit.sub_collections.get(&f.sub, &mut f.working_set).unwrap().is_archived());
+    }
+
+    fn subscribers(f: &mut Fixture) -> u64 {
+        f.reddit.get_sub_reddit(f.sub.clone(), &mut f.working_set).unwrap().subscriber_count
+    }
+
+    #[test]
+    fn subscriptions_update_the_subscriber_count() {
+        let mut f = Fixture::new();
+        let (alice, bob) = (f.user("alice"), f.user("bob"));
+        let other = create_sub(&f.reddit, &alice, "golang", &mut f.working_set);
+
+        f.reddit.subscribe(f.sub.clone(), &alice.0, &mut f.working_set).unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "Subscribed");
+        f.reddit.subscribe(other.clone(), &alice.0, &mut f.working_set).unwrap();
+        f.reddit.subscribe(f.sub.clone(), &bob.0, &mut f.working_set).unwrap();
+        assert_eq!(subscribers(&mut f), 2);
+
+        let again = f.reddit.subscribe(f.sub.clone(), &alice.0, &mut f.working_set);
+        assert_eq!(error_code(again), "ALREADY_SUBSCRIBED");
+        assert_eq!(subscribers(&mut f), 2);
+
+        let subscriptions = f.reddit.get_subscriptions(alice.1.clone(), &mut f.working_set).unwrap().subscriptions;
+        assert_eq!(subscriptions, vec![f.sub.clone(), other.clone()]);
+
+        f.reddit.unsubscribe(f.sub.clone(), &alice.0, &mut f.working_set).unwrap();
+        assert_eq!(last_event_key(&mut f.working_set), "Unsubscribed");
+        assert_eq!(subscribers(&mut f), 1);
+        let subscriptions = f.reddit.get_subscriptions(alice.1.clone(), &mut f.working_set).unwrap().subscriptions;
+        assert_eq!(subscriptions, vec![other]);
+
+        let again = f.reddit.unsubscribe(f.sub.clone(), &alice.0, &mut f.working_set);
+        assert_eq!(error_code(again), "NOT_SUBSCRIBED");
+        assert_eq!(subscribers(&mut f), 1);
+    }
+
+    #[test]
+    fn subscribing_needs_an_existing_subreddit_and_user() {
+        let mut f = Fixture::new();
+        let user = f.user("user");
+
+        let missing = f.reddit.subscribe(get_sub_address::<C>("missing"), &user.0, &mut f.working_set);
+        assert_eq!(error_code(missing), "SUBREDDIT_NOT_FOUND");
+
+        let unregistered = f.reddit.subscribe(f.sub.clone(), &sender("unregistered"), &mut f.working_set);
+        assert_eq!(error_code(unregistered), "SENDER_NOT_REGISTERED");
+        assert_eq!(subscribers(&mut f), 0);
+    }
+}
+
@@ -0,0 +1,140 @@
+//! Helpers shared by the module tests, every test runs against a fresh prover storage
+
+use sov_modules_api::default_context::DefaultContext;
+use sov_modules_api::utils::generate_address;
+use sov_modules_api::{CallResponse, Context, StateMapAccessor, StateValueAccessor, WorkingSet};
+use sov_prover_storage_manager::new_orphan_storage;
+
+use crate::address::{PostAddress, SubAddress, UserAddress};
+use crate::error::RedditError;
+use crate::utils::{get_sub_address, normalize_subname};
+use crate::Reddit;
+
+
+pub(crate) type C = DefaultContext;
+
+
+/// The storage lives in `dir`, which has to outlive the working set
+pub(crate) fn new_working_set(dir: &tempfile::TempDir) -> WorkingSet<C> {
+    WorkingSet::new(new_orphan_storage(dir.path()).unwrap())
+}
+
+
+pub(crate) fn sender(name: &str) -> C {
+    C::new(generate_address::<C>(name), generate_address::<C>("sequencer"), 1)
+}
+
+
+pub(crate) fn set_slot(reddit: &Reddit<C>, slot_height: u64, working_set: &mut WorkingSet<C>) {
+    reddit.current_slot_height.set(&slot_height, working_set);
+}
+
+
+/// Registers a user called `name` and returns its sender context and address
+pub(crate) fn register(reddit: &Reddit<C>, name: &str, working_set: &mut WorkingSet<C>) -> (C, UserAddress<C>) {
+    let context = sender(name);
+    reddit.create_new_user(name, &context, working_set).unwrap();
+    let user_address = reddit.user_address_collections.get(context.sender(), working_set).unwrap();
+    (context, user_address)
+}
+
+
+pub(crate) fn create_sub(
+    reddit: &Reddit<C>,
+    owner: &(C, UserAddress<C>),
+    subname: &str,
+    working_set: &mut WorkingSet<C>,
+) -> SubAddress<C> {
+    reddit
+        .create_new_subreddit(owner.1.clone(), subname, "description", &owner.0, working_set)
+        .unwrap();
+    get_sub_address(&normalize_subname(subname).unwrap())
+}
+
+
+/// Creates a post by `author` and returns its address
+pub(crate) fn create_post(
+    reddit: &Reddit<C>,
+    author: &(C, UserAddress<C>),
+    sub_address: &SubAddress<C>,
+    working_set: &mut WorkingSet<C>,
+) -> PostAddress<C> {
+    let nonce = reddit.post_nonce_collections.get(&author.1, working_set).unwrap_or_default();
+    reddit
+        .create_new_post("title", "flair", "content", sub_address.clone(), &author.0, working_set)
+        .unwrap();
+    reddit.user_post_index.get(&(author.1.clone(), nonce), working_set).unwrap()
+}
+
+
+pub(crate) type Member = (C, UserAddress<C>);
+
+pub(crate) type CallResult = anyhow::Result<CallResponse>;
+
+
+/// Fresh module state with a registered `owner` who created the subreddit `rust`
+pub(crate) struct Fixture {
+    pub(crate) reddit: Reddit<C>,
+    pub(crate) working_set: WorkingSet<C>,
+    pub(crate) owner: Member,
+    pub(crate) sub: SubAddress<C>,
+    _dir: tempfile::TempDir,
+}
+
+impl Fixture {
+    pub(crate) fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let mut working_set = new_working_set(&dir);
+        let reddit = Reddit::<C>::default();
+        let owner = register(&reddit, "owner", &mut working_set);
+        let sub = create_sub(&reddit, &owner, "rust", &mut working_set);
+        Fixture { reddit, working_set, owner, sub, _dir: dir }
+    }
+
+    pub(crate) fn user(&mut self, name: &str) -> Member {
+        register(&self.reddit, name, &mut self.working_set)
+    }
+
+    pub(crate) fn set_slot(&mut self, slot_height: u64) {
+        set_slot(&self.reddit, slot_height, &mut self.working_set);
+    }
+
+    /// Post by `author` in the fixture's subreddit
+    pub(crate) fn post(&mut self, author: &Member) -> PostAddress<C> {
+        create_post(&self.reddit, author, &self.sub, &mut self.working_set)
+    }
+}
+
+
+/// Code of the [`RedditError`] a call failed with
+pub(crate) fn error_code(result: CallResult) -> &'static str {
+    result.unwrap_err().downcast_ref::<RedditError>().expect("reddit error").code()
+}
+
+
+/// Key of the last event added to the working set, drains the events
+pub(crate) fn last_event_key(working_set: &mut WorkingSet<C>) -> String {
+    let event = working_set.take_events().pop().expect("an event");
+    String::from_utf8(event.key().inner().clone()).unwrap()
+}
+
+
+/// CREATED user change with the given change sequence, as the offchain pipeline publishes it
+#[cfg(feature = "native")]
+pub(crate) fn envelope(sequence: u64, slot_height: u64) -> crate::offchain::RedditChangeEnvelope {
+    use crate::offchain::{ChangeType, RedditChangeEnvelope, RedditCollections, RedditStateChanges, SCHEMA_VERSION};
+
+    RedditChangeEnvelope {
+        schema_version: SCHEMA_VERSION,
+        sequence,
+        slot_height,
+        slot_hash: None,
+        tx_hash: None,
+        changes: RedditStateChanges {
+            state: RedditCollections::USER,
+            change: "{}".to_string(),
+            address: format!("user-{}", sequence),
+            change_type: ChangeType::CREATED,
+        },
+    }
+}
@@ -0,0 +1,28 @@
+use sov_modules_api::{Context, StateValueAccessor, WorkingSet};
+
+use crate::Reddit;
+
+
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+/// A point in rollup time: the DA slot a transaction was included in and the
+/// timestamp of that DA block in unix seconds
+pub struct SlotTime {
+    pub slot_height: u64,
+    pub timestamp: i64,
+}
+
+
+impl<C: Context> Reddit<C> {
+    /// Slot currently being executed, as recorded by the begin slot hook
+    pub(crate) fn now(&self, working_set: &mut WorkingSet<C>) -> SlotTime {
+        SlotTime {
+            slot_height: self.current_slot_height.get(working_set).unwrap_or_default(),
+            timestamp: self.current_slot_timestamp.get(working_set).unwrap_or_default(),
+        }
+    }
+}
@@ -1,7 +1,6 @@
-use anyhow::{anyhow, bail};
 use sov_modules_api::{Context, StateMap, StateMapAccessor, WorkingSet};
 
-use crate::{address::{PostAddress, UserAddress}, utils::get_user_address};
+use crate::{address::{PostAddress, UserAddress}, error::RedditError, timestamp::SlotTime, utils::get_user_address};
 
 #[cfg_attr(
     feature = "native",
@@ -12,8 +11,11 @@ use crate::{address::{PostAddress, UserAddress}, utils::get_user_address};
 /// Defines an nft collection
 pub struct User<C: Context> {
     username: String,
-    karma: u64,
-    user_address: UserAddress<C>
+    karma: i64,
+    user_address: UserAddress<C>,
+    owner: C::Address,
+    created_at: SlotTime,
+    last_edited_at: Option<SlotTime>,
 }
 
 
@@ -21,23 +23,25 @@ pub struct User<C: Context> {
 impl<C: Context> User<C> {
  pub fn new(
     username: &str,
+    canonical_username: &str,
     user_collections: &StateMap<UserAddress<C> , User<C>>,
     context: &C,
+    now: SlotTime,
     working_set: &mut WorkingSet<C>
  ) -> anyhow::Result<(UserAddress<C> , User<C>)> {
 
 
     let creator = context.sender();
 
-    let user_address = get_user_address(username, creator.as_ref());
+    let user_address = get_user_address(canonical_username);
 
 
     let user_add = user_collections.get(&user_address, working_set);
 
     if user_add.is_some() {
-        Err(anyhow!( "User with username={} already exists", username ))
+        Err(RedditError::UserAlreadyExists { username: username.to_string() }.into())
     } else {
-        Ok( (user_address , User { username: username.to_string(), karma: 0, user_address:  UserAddress::new(creator)}))
+        Ok( (user_address.clone() , User { username: username.to_string(), karma: 0, user_address, owner: creator.clone(), created_at: now, last_edited_at: None }))
     }
 
 
@@ -49,14 +53,35 @@ impl<C: Context> User<C> {
  }
 
 #[allow(dead_code)]
- pub fn get_karma(&self) -> u64 {
+ pub fn get_karma(&self) -> i64 {
     self.karma
  }
 
+ pub(crate) fn add_karma(&mut self, delta: i64) {
+    self.karma += delta;
+ }
+
 
  #[allow(dead_code)]
  pub fn get_user_address(&self) -> UserAddress<C> {
     self.user_address.clone()
  }
 
+ /// Address of the key that created this user
+ #[allow(dead_code)]
+ pub fn get_owner(&self) -> &C::Address {
+    &self.owner
+ }
+
+ #[allow(dead_code)]
+ pub fn get_created_at(&self) -> SlotTime {
+    self.created_at
+ }
+
+ /// Users have no editable fields yet, so this stays `None`
+ #[allow(dead_code)]
+ pub fn get_last_edited_at(&self) -> Option<SlotTime> {
+    self.last_edited_at
+ }
+
 }
\ No newline at end of file
@@ -1,19 +1,58 @@
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use sov_modules_api::digest::Digest;
-use uuid::Uuid;
 
-use crate::address::{PostAddress, SubAddress, UserAddress};
+use crate::{address::{CommentAddress, PostAddress, SubAddress, UserAddress}, error::RedditError};
 
 
 
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 20;
+pub const SUBNAME_MIN_LEN: usize = 3;
+pub const SUBNAME_MAX_LEN: usize = 21;
 
+
+/// Canonical form of a username, names that differ only in case map to the same user
+pub fn normalize_username(name: &str) -> Result<String, RedditError> {
+    normalize_name("Username", name, USERNAME_MIN_LEN, USERNAME_MAX_LEN)
+}
+
+
+/// Canonical form of a subreddit name, "Rust" and "rust" map to the same subreddit
+pub fn normalize_subname(subname: &str) -> Result<String, RedditError> {
+    normalize_name("Subreddit name", subname, SUBNAME_MIN_LEN, SUBNAME_MAX_LEN)
+}
+
+
+fn normalize_name(kind: &str, name: &str, min_len: usize, max_len: usize) -> Result<String, RedditError> {
+    let canonical = name.to_ascii_lowercase();
+
+    if let Some(c) = canonical
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_' || *c == '-'))
+    {
+        return Err(RedditError::InvalidName {
+            name: name.to_string(),
+            reason: format!("{} contains invalid character {:?}, only letters, digits, '_' and '-' are allowed", kind, c),
+        });
+    }
+
+    if canonical.len() < min_len || canonical.len() > max_len {
+        return Err(RedditError::InvalidName {
+            name: name.to_string(),
+            reason: format!("{} must be between {} and {} characters long", kind, min_len, max_len),
+        });
+    }
+
+    Ok(canonical)
+}
+
+
+/// Expects the canonical username, see [`normalize_username`]
 pub fn get_user_address<C: sov_modules_api::Context>(
     name: &str,
-    sender: &[u8],
 ) -> UserAddress<C> {
     let mut hasher = C::Hasher::new();
-    hasher.update(sender);
     hasher.update(name.as_bytes());
 
     let hash: [u8; 32] = hasher.finalize().into();
@@ -21,6 +60,7 @@ pub fn get_user_address<C: sov_modules_api::Context>(
 }
 
 
+/// Expects the canonical subreddit name, see [`normalize_subname`]
 pub fn get_sub_address<C: sov_modules_api::Context>(
     subname: &str
 ) -> SubAddress<C> {
@@ -33,17 +73,108 @@ pub fn get_sub_address<C: sov_modules_api::Context>(
 }
 
 
+/// Post addresses only depend on consensus data, `nonce` is the number of posts
+/// the author has created before this one
 pub fn get_post_address<C: sov_modules_api::Context>(
     user_address: &[u8],
     sub_address: &[u8],
+    nonce: u64,
 ) -> PostAddress<C> {
-    let new_uuid = Uuid::new_v4();
     let mut hasher = C::Hasher::new();
 
     hasher.update(user_address);
     hasher.update(sub_address);
-    hasher.update(new_uuid.as_bytes());
+    hasher.update(nonce.to_le_bytes());
 
     let hash: [u8; 32] = hasher.finalize().into();
     PostAddress::new(&C::Address::from(hash))
-}
\ No newline at end of file
+}
+
+
+/// Comment addresses only depend on state, the position of the comment under its
+/// parent (or under the post for top level comments) makes every address unique
+pub fn get_comment_address<C: sov_modules_api::Context>(
+    user_address: &[u8],
+    post_address: &[u8],
+    parent_comment: Option<&[u8]>,
+    index: u64,
+) -> CommentAddress<C> {
+    let mut hasher = C::Hasher::new();
+
+    hasher.update(user_address);
+    hasher.update(post_address);
+    if let Some(parent) = parent_comment {
+        hasher.update(parent);
+    }
+    hasher.update(index.to_le_bytes());
+
+    let hash: [u8; 32] = hasher.finalize().into();
+    CommentAddress::new(&C::Address::from(hash))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::Module;
+
+    use super::*;
+    use crate::ratelimit::RateLimits;
+    use crate::test_utils::{error_code, sender, Fixture};
+    use crate::RedditConfig;
+
+    fn invalid_reason(result: Result<String, RedditError>) -> String {
+        match result {
+            Err(RedditError::InvalidName { reason, .. }) => reason,
+            other => panic!("expected an invalid name, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn names_are_lowercased() {
+        assert_eq!(normalize_username("Alice_01").unwrap(), "alice_01");
+        assert_eq!(normalize_subname("Rust-Lang").unwrap(), "rust-lang");
+    }
+
+    #[test]
+    fn name_lengths_are_bounded() {
+        let shortest = "a".repeat(USERNAME_MIN_LEN);
+        let longest = "a".repeat(USERNAME_MAX_LEN);
+        assert_eq!(normalize_username(&shortest).unwrap(), shortest);
+        assert_eq!(normalize_username(&longest).unwrap(), longest);
+        assert!(invalid_reason(normalize_username(&shortest[1..])).contains("between 3 and 20"));
+        assert!(invalid_reason(normalize_username(&format!("{}a", longest))).contains("between 3 and 20"));
+
+        assert!(normalize_subname(&"a".repeat(SUBNAME_MAX_LEN)).is_ok());
+        assert!(invalid_reason(normalize_subname(&"a".repeat(SUBNAME_MAX_LEN + 1))).contains("between 3 and 21"));
+        assert!(invalid_reason(normalize_subname(&"a".repeat(SUBNAME_MIN_LEN - 1))).contains("between 3 and 21"));
+    }
+
+    #[test]
+    fn names_only_take_ascii_letters_digits_and_dashes() {
+        for name in ["with space", "dot.name", "émile", "ｒｕｓｔ", "semi;colon", ""] {
+            assert!(normalize_username(name).is_err(), "{:?} was accepted", name);
+        }
+        assert!(invalid_reason(normalize_subname("ru st")).contains("invalid character ' '"));
+        assert!(invalid_reason(normalize_username("ééé")).contains("invalid character 'é'"));
+    }
+
+    #[test]
+    fn reserved_names_are_rejected_in_any_case() {
+        let mut f = Fixture::new();
+        let config = RedditConfig {
+            reserved_names: vec!["Admin".to_string()],
+            archive_after_slots: None,
+            rate_limits: RateLimits::default(),
+        };
+        f.reddit.genesis(&config, &mut f.working_set).unwrap();
+
+        let user = f.reddit.create_new_user("ADMIN", &sender("admin"), &mut f.working_set);
+        assert_eq!(error_code(user), "NAME_RESERVED");
+
+        let owner = f.owner.clone();
+        let sub = f.reddit.create_new_subreddit(owner.1.clone(), "admin", "", &owner.0, &mut f.working_set);
+        assert_eq!(error_code(sub), "NAME_RESERVED");
+
+        f.reddit.create_new_user("admins", &sender("admins"), &mut f.working_set).unwrap();
+    }
+}
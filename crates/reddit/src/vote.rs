@@ -0,0 +1,117 @@
+#[cfg_attr(
+    feature = "native",
+    derive(serde::Serialize),
+    derive(serde::Deserialize),
+    derive(schemars::JsonSchema),
+    derive(clap::ValueEnum)
+)]
+#[derive(borsh::BorshDeserialize, borsh::BorshSerialize, Debug, PartialEq, Eq, Clone, Copy)]
+/// Direction of a vote on a post, `NONE` retracts a previous vote
+pub enum VoteDirection {
+    UP,
+    DOWN,
+    NONE,
+}
+
+impl VoteDirection {
+    /// Contribution of this vote to the post score
+    pub fn value(&self) -> i64 {
+        match self {
+            VoteDirection::UP => 1,
+            VoteDirection::DOWN => -1,
+            VoteDirection::NONE => 0,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sov_modules_api::StateMapAccessor;
+
+    use super::VoteDirection;
+    use crate::address::PostAddress;
+    use crate::test_utils::{Fixture, Member, C};
+
+    /// The owner's post and a second user voting on it
+    fn setup() -> (Fixture, Member, PostAddress<C>) {
+        let mut f = Fixture::new();
+        let post = f.post(&f.owner.clone());
+        let voter = f.user("voter");
+        (f, voter, post)
+    }
+
+    fn vote(f: &mut Fixture, voter: &Member, post: &PostAddress<C>, direction: VoteDirection) {
+        f.reddit.vote_on_post(post.clone(), direction, &voter.0, &mut f.working_set).unwrap();
+    }
+
+    /// Score, upvotes, downvotes of the post and karma of its author
+    fn tally(f: &mut Fixture, post: &PostAddress<C>) -> (i64, u64, u64, i64) {
+        let post = f.reddit.post_collections.get(post, &mut f.working_set).unwrap();
+        let author = f.reddit.user_collections.get(&f.owner.1, &mut f.working_set).unwrap();
+        (post.get_score(), post.get_upvotes(), post.get_downvotes(), author.get_karma())
+    }
+
+    fn stored_vote(f: &mut Fixture, voter: &Member, post: &PostAddress<C>) -> Option<VoteDirection> {
+        f.reddit.post_vote_collections.get(&(post.clone(), voter.1.clone()), &mut f.working_set)
+    }
+
+    #[test]
+    fn upvote_accrues_karma() {
+        let (mut f, voter, post) = setup();
+        vote(&mut f, &voter, &post, VoteDirection::UP);
+        assert_eq!(tally(&mut f, &post), (1, 1, 0, 1));
+        assert_eq!(stored_vote(&mut f, &voter, &post), Some(VoteDirection::UP));
+    }
+
+    #[test]
+    fn flipping_a_vote_moves_score_by_two() {
+        let (mut f, voter, post) = setup();
+        vote(&mut f, &voter, &post, VoteDirection::UP);
+        vote(&mut f, &voter, &post, VoteDirection::DOWN);
+        assert_eq!(tally(&mut f, &post), (-1, 0, 1, -1));
+
+        vote(&mut f, &voter, &post, VoteDirection::UP);
+        assert_eq!(tally(&mut f, &post), (1, 1, 0, 1));
+    }
+
+    #[test]
+    fn retracting_removes_the_vote_and_its_karma() {
+        let (mut f, voter, post) = setup();
+        vote(&mut f, &voter, &post, VoteDirection::DOWN);
+        vote(&mut f, &voter, &post, VoteDirection::NONE);
+        assert_eq!(tally(&mut f, &post), (0, 0, 0, 0));
+        assert_eq!(stored_vote(&mut f, &voter, &post), None);
+    }
+
+    #[test]
+    fn revoting_after_retracting_counts_once() {
+        let (mut f, voter, post) = setup();
+        vote(&mut f, &voter, &post, VoteDirection::UP);
+        vote(&mut f, &voter, &post, VoteDirection::NONE);
+        vote(&mut f, &voter, &post, VoteDirection::UP);
+        assert_eq!(tally(&mut f, &post), (1, 1, 0, 1));
+    }
+
+    #[test]
+    fn repeating_a_vote_changes_nothing() {
+        let (mut f, voter, post) = setup();
+        vote(&mut f, &voter, &post, VoteDirection::DOWN);
+        vote(&mut f, &voter, &post, VoteDirection::DOWN);
+        assert_eq!(tally(&mut f, &post), (-1, 0, 1, -1));
+
+        vote(&mut f, &voter, &post, VoteDirection::NONE);
+        vote(&mut f, &voter, &post, VoteDirection::NONE);
+        assert_eq!(tally(&mut f, &post), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn votes_of_different_users_add_up() {
+        let (mut f, voter, post) = setup();
+        vote(&mut f, &voter, &post, VoteDirection::UP);
+
+        let other = f.user("other");
+        vote(&mut f, &other, &post, VoteDirection::DOWN);
+        assert_eq!(tally(&mut f, &post), (0, 1, 1, 0));
+    }
+}
@@ -2,6 +2,8 @@
 
 use anyhow::Context;
 use clap::Parser;
+use reddit::offchain::{init_sink, OffchainSinkConfig};
+use serde::Deserialize;
 use sov_celestia_adapter::CelestiaConfig;
 use sov_modules_rollup_blueprint::{Rollup, RollupBlueprint};
 use sov_modules_stf_blueprint::kernels::basic::BasicKernelGenesisConfig;
@@ -37,6 +39,13 @@ struct Args {
     kernel_genesis_paths: String,
 }
 
+/// Node-only settings that live next to the rollup config in the same TOML file
+#[derive(Deserialize, Debug, Default)]
+struct NodeOffchainConfig {
+    #[serde(default)]
+    reddit_offchain: OffchainSinkConfig,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     // Initializing logging
@@ -79,6 +88,10 @@ async fn new_rollup(
     let rollup_config: RollupConfig<CelestiaConfig> =
         from_toml_path(rollup_config_path).context("Failed to read rollup configuration")?;
 
+    let offchain_config: NodeOffchainConfig = from_toml_path(rollup_config_path)
+        .context("Failed to read reddit offchain configuration")?;
+    init_sink(&offchain_config.reddit_offchain, &rollup_config.storage.path)?;
+
     let kernel_genesis = BasicKernelGenesisConfig {
         chain_state: serde_json::from_str(
             &std::fs::read_to_string(&kernel_genesis_paths.chain_state)
@@ -2,6 +2,7 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context as _};
 use sov_accounts::AccountConfig;
+use reddit::RedditConfig;
 use sov_bank::BankConfig;
 use sov_modules_api::{Context, DaSpec};
 use sov_modules_stf_blueprint::Runtime as RuntimeTrait;
@@ -19,6 +20,8 @@ pub struct GenesisPaths {
     pub bank_genesis_path: PathBuf,
     /// Sequencer Registry genesis path.
     pub sequencer_genesis_path: PathBuf,
+    /// Reddit genesis path.
+    pub reddit_genesis_path: PathBuf,
 }
 
 impl GenesisPaths {
@@ -32,6 +35,7 @@ impl GenesisPaths {
             accounts_genesis_path: dir.as_ref().join("accounts.json"),
             bank_genesis_path: dir.as_ref().join("bank.json"),
             sequencer_genesis_path: dir.as_ref().join("sequencer_registry.json"),
+            reddit_genesis_path: dir.as_ref().join("reddit.json"),
         }
     }
 }
@@ -77,10 +81,12 @@ fn create_genesis_config<C: Context, Da: DaSpec>(
     let bank_config: BankConfig<C> = read_json_file(&genesis_paths.bank_genesis_path)?;
     let sequencer_registry_config: SequencerConfig<C, Da> =
         read_json_file(&genesis_paths.sequencer_genesis_path)?;
+    let reddit_config: RedditConfig = read_json_file(&genesis_paths.reddit_genesis_path)?;
 
     Ok(GenesisConfig::new(
         accounts_config,
         bank_config,
         sequencer_registry_config,
+        reddit_config,
     ))
 }
@@ -5,6 +5,7 @@
 //! - At the beginning and end of each slot (DA layer block)
 
 use super::runtime::Runtime;
+use reddit::Reddit;
 use sov_accounts::AccountsTxHook;
 use sov_bank::BankTxHook;
 use sov_modules_api::hooks::{ApplyBlobHooks, FinalizeHook, SlotHooks, TxHooks};
@@ -34,6 +35,8 @@ impl<C: Context, Da: DaSpec> TxHooks for Runtime<C, Da> {
         let hook = BankTxHook { sender, sequencer };
         self.bank.pre_dispatch_tx_hook(tx, working_set, &hook)?;
 
+        self.reddit.pre_dispatch_tx_hook(tx, working_set, &arg.sequencer)?;
+
         Ok(C::new(hook.sender, hook.sequencer, *height))
     }
 
@@ -45,6 +48,7 @@ impl<C: Context, Da: DaSpec> TxHooks for Runtime<C, Da> {
     ) -> anyhow::Result<()> {
         self.accounts.post_dispatch_tx_hook(tx, ctx, working_set)?;
         self.bank.post_dispatch_tx_hook(tx, ctx, working_set)?;
+        self.reddit.post_dispatch_tx_hook(tx, ctx, working_set)?;
         Ok(())
     }
 }
@@ -101,14 +105,24 @@ impl<C: Context, Da: DaSpec> SlotHooks<Da> for Runtime<C, Da> {
 
     fn begin_slot_hook(
         &self,
-        _slot_header: &Da::BlockHeader,
-        _validity_condition: &Da::ValidityCondition,
-        _pre_state_root: &<<Self::Context as Spec>::Storage as Storage>::Root,
-        _working_set: &mut sov_modules_api::WorkingSet<C>,
+        slot_header: &Da::BlockHeader,
+        validity_condition: &Da::ValidityCondition,
+        pre_state_root: &<<Self::Context as Spec>::Storage as Storage>::Root,
+        working_set: &mut sov_modules_api::WorkingSet<C>,
     ) {
+        <Reddit<C> as SlotHooks<Da>>::begin_slot_hook(
+            &self.reddit,
+            slot_header,
+            validity_condition,
+            pre_state_root,
+            working_set,
+        );
     }
 
-    fn end_slot_hook(&self, _working_set: &mut sov_modules_api::WorkingSet<C>) {}
+    fn end_slot_hook(&self, working_set: &mut sov_modules_api::WorkingSet<C>) {
+        // Archive posts that expired in this slot
+        <Reddit<C> as SlotHooks<Da>>::end_slot_hook(&self.reddit, working_set);
+    }
 }
 
 impl<C: Context, Da: sov_modules_api::DaSpec> FinalizeHook<Da> for Runtime<C, Da> {
@@ -116,8 +130,14 @@ impl<C: Context, Da: sov_modules_api::DaSpec> FinalizeHook<Da> for Runtime<C, Da
 
     fn finalize_hook(
         &self,
-        _root_hash: &<<Self::Context as Spec>::Storage as Storage>::Root,
-        _accessory_working_set: &mut AccessoryWorkingSet<C>,
+        root_hash: &<<Self::Context as Spec>::Storage as Storage>::Root,
+        accessory_working_set: &mut AccessoryWorkingSet<C>,
     ) {
+        // Move the posts that changed in this slot to their new place in the subreddit feeds
+        <Reddit<C> as FinalizeHook<Da>>::finalize_hook(
+            &self.reddit,
+            root_hash,
+            accessory_working_set,
+        );
     }
 }
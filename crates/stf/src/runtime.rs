@@ -15,6 +15,8 @@ use sov_modules_api::Spec;
 use sov_modules_api::{Context, DaSpec, DispatchCall, Genesis, MessageCodec};
 #[cfg(feature = "native")]
 pub use sov_sequencer_registry::{SequencerRegistryRpcImpl, SequencerRegistryRpcServer};
+#[cfg(feature = "native")]
+pub use reddit::query::{RedditRpcImpl, RedditRpcServer};
 
 #[cfg(feature = "native")]
 use crate::genesis_config::GenesisPaths;
@@ -64,6 +66,8 @@ pub struct Runtime<C: Context, Da: DaSpec> {
     pub bank: sov_bank::Bank<C>,
     /// The sequencer registry module is responsible for authorizing users to sequencer rollup transactions
     pub sequencer_registry: sov_sequencer_registry::SequencerRegistry<C, Da>,
+    /// The reddit module stores users, subreddits and posts of the social app
+    pub reddit: reddit::Reddit<C>,
 }
 
 impl<C, Da> sov_modules_stf_blueprint::Runtime<C, Da> for Runtime<C, Da>